@@ -1,9 +1,11 @@
-use rand::Rng;
-
 use variant_type::FLAG_IMMUTABLE;
 
 use crate::{
-    particle::{self, Particle, Velocity},
+    events::Event,
+    metadata::Value,
+    particle::{self, Particle},
+    rng,
+    variant::Variant,
     variant_type, world,
 };
 
@@ -14,7 +16,7 @@ pub struct API<'a> {
 }
 
 impl<'a> API<'a> {
-    pub fn set(&mut self, dx: i32, dy: i32, particle: particle::Particle) {
+    pub fn set(&mut self, dx: i32, dy: i32, mut particle: particle::Particle) {
         if !(-2..=2).contains(&dx) || !(-2..=2).contains(&dy) {
             panic!("oob set")
         }
@@ -23,14 +25,57 @@ impl<'a> API<'a> {
         let ny = self.y + dy;
 
         if nx < 0 || nx > self.world.width - 1 || ny < 0 || ny > self.world.height - 1 {
+            if particle.get_variant() != Variant::Empty {
+                self.emit(Event::LeftWorld {
+                    x: nx,
+                    y: ny,
+                    variant: particle.get_variant(),
+                });
+            }
             return;
         }
         let idx = self.world.get_idx(nx, ny);
 
+        // reactions build particles from the compiled in types, pick up overridden ones here
+        if particle.variant_type.custom == 0 {
+            if let Some(variant_type) = self.world.elements.overridden(particle.get_variant()) {
+                particle.variant_type = variant_type;
+            }
+        }
+        // the clock always moves on, only real changes are kept for renderers and stats
+        particle.clock = self.world.particles[idx].clock;
+        if self.world.particles[idx] != particle {
+            self.world.modified_indices.insert(idx);
+        }
         self.world.particles[idx] = particle;
         self.world.particles[idx].clock = self.world.generation.wrapping_add(1);
     }
 
+    // element state, see `metadata`. None if the particle's element has no such field
+    pub fn get_field(&self, particle: &Particle, name: &str) -> Option<Value> {
+        self.world.get_field(particle, name)
+    }
+
+    // only changes `particle`, it still has to be `set` into the world
+    pub fn set_field(
+        &mut self,
+        particle: &mut Particle,
+        name: &str,
+        value: Value,
+    ) -> Result<(), String> {
+        self.world.set_field(particle, name, value)
+    }
+
+    // whether the cell at the offset is part of the world
+    pub fn inside(&self, dx: i32, dy: i32) -> bool {
+        let (x, y) = (self.x + dx, self.y + dy);
+        x >= 0 && x < self.world.width && y >= 0 && y < self.world.height
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        self.world.events.push(event);
+    }
+
     pub fn get_nbrs(&mut self) -> Vec<Particle> {
         let mut nbrs = Vec::new();
         for dx in -1..=1 {
@@ -54,13 +99,14 @@ impl<'a> API<'a> {
     }
 
     pub fn swap(&mut self, idx0: usize, idx1: usize) {
-        if idx0 > self.world.particles.len() || idx1 > self.world.particles.len() {
+        if idx0 >= self.world.particles.len() || idx1 >= self.world.particles.len() {
             return;
         }
         let mut c1 = self.world.particles[idx0];
         let mut c2 = self.world.particles[idx1];
 
-        if c1.variant_type.flags & FLAG_IMMUTABLE | c2.variant_type.flags & FLAG_IMMUTABLE == 0 {
+        // immutable particles stay where they are
+        if (c1.variant_type.flags | c2.variant_type.flags) & FLAG_IMMUTABLE != 0 {
             return;
         }
 
@@ -68,6 +114,7 @@ impl<'a> API<'a> {
         c2.modified = self.world.modified_state;
         self.world.particles[idx0] = c2;
         self.world.particles[idx1] = c1;
+        self.world.modified_indices.extend([idx0, idx1]);
     }
 
     pub fn swap_dirty(&mut self, x1: i32, y1: i32, x2: i32, y2: i32) {
@@ -76,7 +123,8 @@ impl<'a> API<'a> {
         let mut c1 = self.world.particles[idx1];
         let mut c2 = self.world.particles[idx2];
 
-        if c1.variant_type.flags & FLAG_IMMUTABLE | c2.variant_type.flags & FLAG_IMMUTABLE == 0 {
+        // immutable particles stay where they are
+        if (c1.variant_type.flags | c2.variant_type.flags) & FLAG_IMMUTABLE != 0 {
             return;
         }
 
@@ -84,6 +132,7 @@ impl<'a> API<'a> {
         c2.modified = self.world.modified_state;
         self.world.particles[idx1] = c2;
         self.world.particles[idx2] = c1;
+        self.world.modified_indices.extend([idx1, idx2]);
     }
     pub fn get_from_idx(&mut self, idx: usize) -> Particle {
         self.world.particles[idx]
@@ -104,14 +153,17 @@ impl<'a> API<'a> {
     }
 
     pub fn get_pressure(&mut self, x: i32, y: i32) -> f32 {
-        let idx = self.world.get_idx(x, y);
-        self.world.environment[idx].ambient_pressure
+        self.world.get_pressure(x, y)
     }
 
     pub fn set_pressure(&mut self, x: i32, y: i32, pressure: f32) {
-        let idx = self.world.get_idx(x, y);
-        self.world.environment[idx].ambient_pressure = pressure;
+        self.world.set_pressure(x, y, pressure);
     }
+    // gravity at this cell as a neighbour offset
+    pub fn gravity(&mut self) -> (i32, i32) {
+        self.world.gravity.direction_at(self.x, self.y)
+    }
+
     pub fn rand_vec(&mut self) -> (i32, i32) {
         let i = self.rand_int(2000);
         match i % 9 {
@@ -142,29 +194,14 @@ impl<'a> API<'a> {
     }
 
     pub fn rand_int(&mut self, n: i32) -> i32 {
-        rand::thread_rng().gen_range(0..n)
+        rng::rand_int(n)
     }
 
     pub fn get(&mut self, dx: i32, dy: i32) -> Particle {
         if !(-2..=2).contains(&dx) || !(-2..=2).contains(&dy) {
             panic!("oob set");
         }
-        let nx = self.x + dx;
-        let ny = self.y + dy;
-
-        if nx < 0 || nx > self.world.width - 1 || ny < 0 || ny > self.world.height - 1 {
-            return Particle {
-                variant_type: variant_type::WALL,
-                ra: 0,
-                rb: 0,
-                clock: self.world.generation,
-                strength: 0,
-                modified: false,
-                velocity: Velocity { x: 0., y: 0. },
-                temperature: 0.,
-            };
-        }
-        self.world.get_particle(nx, ny)
+        self.world.nbr_at(self.x + dx, self.y + dy)
     }
 }
 
@@ -214,4 +251,26 @@ mod tests {
         assert_eq!(api.get(0, 0).get_variant(), Variant::Empty);
         assert_eq!(api.get(1, 1).get_variant(), Variant::Empty);
     }
+
+    #[test]
+    fn test_swap() {
+        let mut world = world::World::new(4, 4);
+        world.set_particle(0, 0, Variant::Sand);
+        world.set_particle(1, 0, Variant::Water);
+        world.set_particle(2, 0, Variant::Wall);
+        let mut api = API {
+            world: &mut world,
+            x: 0,
+            y: 0,
+        };
+        api.swap(0, 1);
+        assert_eq!(api.get(0, 0).get_variant(), Variant::Water);
+        assert_eq!(api.get(1, 0).get_variant(), Variant::Sand);
+
+        // walls are immutable
+        api.swap_dirty(1, 0, 2, 0);
+        assert_eq!(api.get(1, 0).get_variant(), Variant::Sand);
+        api.swap(0, 16);
+        assert_eq!(api.get(0, 0).get_variant(), Variant::Water);
+    }
 }
@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::API,
+    events::Event,
+    particle::Particle,
+    variant::Variant,
+    variant_type::{VariantProperty, VariantType},
+    world::World,
+};
+
+// amounts are in "cells worth" of gas, a gas particle carries one whole unit
+pub const PARTICLE_AMOUNT: f32 = 1.0;
+// a cell holding this much of one gas condenses a particle out of the mix
+pub const CONDENSE_AT: f32 = 2.0;
+// gas particles only dissolve into cells holding less than this, leaving room before CONDENSE_AT
+pub const DISSOLVE_BELOW: f32 = 1.0;
+pub const DIFFUSION: f32 = 0.2;
+
+pub const AIR: GasMix = GasMix {
+    o2: 0.21,
+    n2: 0.78,
+    co2: 0.0004,
+    h2o: 0.01,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gas {
+    O2,
+    N2,
+    CO2,
+    H2O,
+}
+
+impl Gas {
+    pub const ALL: [Gas; 4] = [Gas::O2, Gas::N2, Gas::CO2, Gas::H2O];
+
+    pub fn from_variant(variant: Variant) -> Option<Gas> {
+        match variant {
+            Variant::OXGN => Some(Gas::O2),
+            Variant::NITR => Some(Gas::N2),
+            Variant::CO2 => Some(Gas::CO2),
+            Variant::WTVP => Some(Gas::H2O),
+            _ => None,
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        match self {
+            Gas::O2 => Variant::OXGN,
+            Gas::N2 => Variant::NITR,
+            Gas::CO2 => Variant::CO2,
+            Gas::H2O => Variant::WTVP,
+        }
+    }
+}
+
+// partial amounts of each gas in one environment cell
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GasMix {
+    pub o2: f32,
+    pub n2: f32,
+    pub co2: f32,
+    pub h2o: f32,
+}
+
+impl GasMix {
+    pub fn get(&self, gas: Gas) -> f32 {
+        match gas {
+            Gas::O2 => self.o2,
+            Gas::N2 => self.n2,
+            Gas::CO2 => self.co2,
+            Gas::H2O => self.h2o,
+        }
+    }
+
+    fn get_mut(&mut self, gas: Gas) -> &mut f32 {
+        match gas {
+            Gas::O2 => &mut self.o2,
+            Gas::N2 => &mut self.n2,
+            Gas::CO2 => &mut self.co2,
+            Gas::H2O => &mut self.h2o,
+        }
+    }
+
+    pub fn add(&mut self, gas: Gas, amount: f32) {
+        *self.get_mut(gas) += amount;
+    }
+
+    // removes up to `amount`, returns how much was actually there
+    pub fn take(&mut self, gas: Gas, amount: f32) -> f32 {
+        let value = self.get_mut(gas);
+        let taken = amount.min(*value).max(0.);
+        *value -= taken;
+        taken
+    }
+
+    pub fn total(&self) -> f32 {
+        self.o2 + self.n2 + self.co2 + self.h2o
+    }
+}
+
+// gas moves through empty space and gas particles, everything else blocks it
+fn is_open(particle: &Particle) -> bool {
+    particle.get_variant() == Variant::Empty
+        || particle.variant_type.variant_property == VariantProperty::Gas
+}
+
+// one atmosphere step: condense particles out of cells that hold too much
+// of one gas, then diffuse the mix between open neighbours
+pub(crate) fn step(world: &mut World) {
+    for idx in 0..world.particles.len() {
+        if world.particles[idx].get_variant() != Variant::Empty {
+            continue;
+        }
+
+        let gas = Gas::ALL
+            .into_iter()
+            .find(|gas| world.environment[idx].gas.get(*gas) >= CONDENSE_AT);
+        if let Some(gas) = gas {
+            world.environment[idx].gas.take(gas, PARTICLE_AMOUNT);
+            let (x, y) = (idx as i32 % world.width, idx as i32 / world.width);
+            let mut api = API { world, x, y };
+            api.set(
+                0,
+                0,
+                Particle::new(*VariantType::from_variant(gas.variant()), 0, 0),
+            );
+            api.emit(Event::PhaseChange {
+                x,
+                y,
+                from: Variant::Empty,
+                to: gas.variant(),
+            });
+        }
+    }
+
+    for y in 0..world.height {
+        for x in 0..world.width {
+            let idx = world.get_idx(x, y);
+            if !is_open(&world.particles[idx]) {
+                continue;
+            }
+
+            // pairwise exchange with right and bottom neighbours keeps the total constant
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx >= world.width || ny >= world.height {
+                    continue;
+                }
+                let nidx = world.get_idx(nx, ny);
+                if !is_open(&world.particles[nidx]) {
+                    continue;
+                }
+
+                for gas in Gas::ALL {
+                    let a = world.environment[idx].gas.get(gas);
+                    let b = world.environment[nidx].gas.get(gas);
+                    let flux = (a - b) * DIFFUSION * 0.25;
+                    *world.environment[idx].gas.get_mut(gas) -= flux;
+                    *world.environment[nidx].gas.get_mut(gas) += flux;
+                }
+            }
+        }
+    }
+}
+
+// gas particles fade into the mix of their cell instead of vanishing,
+// returns true if the particle dissolved
+pub fn dissolve(particle: &Particle, api: &mut API) -> bool {
+    let gas = match Gas::from_variant(particle.get_variant()) {
+        Some(gas) => gas,
+        None => return false,
+    };
+
+    // the particle already moved this tick, (0, 0) holds something else
+    if api.get(0, 0).get_variant() != particle.get_variant() {
+        return false;
+    }
+
+    let idx = api.get_idx(api.x, api.y);
+    if api.world.environment[idx].gas.get(gas) >= DISSOLVE_BELOW || !api.once_in(20) {
+        return false;
+    }
+
+    api.world.environment[idx].gas.add(gas, PARTICLE_AMOUNT);
+    api.set(0, 0, crate::variant::EMPTY_CELL);
+    true
+}
+
+// swap `amount` of one gas for another with the richest open cell around (and including) this one.
+// returns how much was taken, without an atmosphere there's always enough
+pub fn breathe(api: &mut API, take: Gas, give: Gas, amount: f32) -> f32 {
+    if !api.world.atmosphere {
+        return amount;
+    }
+
+    match richest(api.world, api.x, api.y, take) {
+        Some((idx, _)) => {
+            let mix = &mut api.world.environment[idx].gas;
+            let taken = mix.take(take, amount);
+            mix.add(give, taken);
+            taken
+        }
+        None => 0.,
+    }
+}
+
+// the open cell around (and including) (x, y) holding the most of a gas, and how much it holds
+pub(crate) fn richest(world: &World, x: i32, y: i32, gas: Gas) -> Option<(usize, f32)> {
+    let mut best: Option<(usize, f32)> = None;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || nx > world.width - 1 || ny < 0 || ny > world.height - 1 {
+                continue;
+            }
+            if (dx, dy) != (0, 0) && !is_open(&world.read_particle(nx, ny)) {
+                continue;
+            }
+
+            let idx = world.get_idx(nx, ny);
+            let available = world.environment[idx].gas.get(gas);
+            if best.is_none_or(|(_, b)| available > b) {
+                best = Some((idx, available));
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Value;
+
+    fn total(world: &World, gas: Gas) -> f32 {
+        let mix: f32 = world.environment.iter().map(|e| e.gas.get(gas)).sum();
+        let particles = world
+            .particles
+            .iter()
+            .filter(|p| Gas::from_variant(p.get_variant()) == Some(gas))
+            .count();
+        mix + particles as f32 * PARTICLE_AMOUNT
+    }
+
+    #[test]
+    fn test_diffusion_conserves_gas() {
+        let mut world = World::new(8, 8);
+        world.set_atmosphere(true);
+        let idx = world.get_idx(3, 3);
+        world.environment[idx].gas.co2 = 1.5;
+        let before = total(&world, Gas::CO2);
+        for _ in 0..50 {
+            world.tick();
+        }
+        assert!((total(&world, Gas::CO2) - before).abs() < 1e-3);
+        assert!(world.get_gas(3, 3).co2 < 0.5);
+        assert!(world.get_gas(0, 0).co2 > AIR.co2);
+    }
+
+    #[test]
+    fn test_fire_needs_oxygen() {
+        // sealed box of fire, it has to go out once the oxygen is used up
+        let mut world = World::new(5, 5);
+        world.set_atmosphere(true);
+        for x in 0..5 {
+            for y in 0..5 {
+                world.set_particle(x, y, Variant::Wall);
+            }
+        }
+        world.erase_indestructible(2, 2);
+        world.set_particle(2, 2, Variant::Fire);
+        let idx = world.get_idx(2, 2);
+        // outlive the oxygen so it's the air that puts it out
+        let mut fire = world.particles[idx];
+        world
+            .set_field(&mut fire, "life", Value::U16(u16::MAX))
+            .unwrap();
+        world.particles[idx] = fire;
+
+        let before = world.get_gas(2, 2);
+        for _ in 0..200 {
+            world.tick();
+        }
+        let after = world.get_gas(2, 2);
+        assert_ne!(world.get_particle(2, 2).get_variant(), Variant::Fire);
+        assert!(after.o2 < before.o2 * 0.5);
+        assert!(after.co2 > before.co2);
+    }
+
+    #[test]
+    fn test_gas_dissolves_and_condenses() {
+        let mut world = World::new(6, 6);
+        world.set_atmosphere(true);
+        world.set_particle(2, 2, Variant::NITR);
+        let before = total(&world, Gas::N2);
+        for _ in 0..200 {
+            world.tick();
+        }
+        assert_eq!(world.get_particle_count(), 0);
+        assert!((total(&world, Gas::N2) - before).abs() < 1e-3);
+
+        // too much co2 in one cell condenses into a particle
+        let idx = world.get_idx(4, 4);
+        world.environment[idx].gas.co2 = CONDENSE_AT;
+        world.tick();
+        assert_eq!(world.get_particle(4, 4).get_variant(), Variant::CO2);
+        assert!(world.modified_indices.contains(&idx));
+        let events: Vec<Event> = world.drain_events().collect();
+        assert!(events.contains(&Event::PhaseChange {
+            x: 4,
+            y: 4,
+            from: Variant::Empty,
+            to: Variant::CO2,
+        }));
+    }
+}
@@ -124,6 +124,212 @@ pub const GLASS_COLOR: ParticleColor = ParticleColor {
     a: 255,
 };
 
+pub const HIGHLIFE_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 96,
+    b: 196,
+    a: 255,
+};
+
+pub const SEEDS_COLOR: ParticleColor = ParticleColor {
+    r: 120,
+    g: 255,
+    b: 110,
+    a: 255,
+};
+
+pub const DAY_NIGHT_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 200,
+    b: 60,
+    a: 255,
+};
+
+pub const SPARK_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 250,
+    b: 170,
+    a: 255,
+};
+
+pub const BATTERY_COLOR: ParticleColor = ParticleColor {
+    r: 70,
+    g: 150,
+    b: 70,
+    a: 255,
+};
+
+pub const SWITCH_COLOR: ParticleColor = ParticleColor {
+    r: 200,
+    g: 180,
+    b: 60,
+    a: 255,
+};
+
+pub const WIRE_COLOR: ParticleColor = ParticleColor {
+    r: 184,
+    g: 115,
+    b: 51,
+    a: 255,
+};
+
+pub const WIRE_HEAD_COLOR: ParticleColor = ParticleColor {
+    r: 60,
+    g: 140,
+    b: 255,
+    a: 255,
+};
+
+pub const WIRE_TAIL_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 70,
+    b: 40,
+    a: 255,
+};
+
+pub const LAVA_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 90,
+    b: 0,
+    a: 255,
+};
+
+pub const STONE_COLOR: ParticleColor = ParticleColor {
+    r: 110,
+    g: 104,
+    b: 98,
+    a: 255,
+};
+
+pub const ICE_COLOR: ParticleColor = ParticleColor {
+    r: 170,
+    g: 220,
+    b: 255,
+    a: 255,
+};
+
+pub const OIL_COLOR: ParticleColor = ParticleColor {
+    r: 60,
+    g: 40,
+    b: 20,
+    a: 255,
+};
+
+pub const ACID_COLOR: ParticleColor = ParticleColor {
+    r: 150,
+    g: 255,
+    b: 40,
+    a: 255,
+};
+
+pub const PLANT_COLOR: ParticleColor = ParticleColor {
+    r: 40,
+    g: 160,
+    b: 40,
+    a: 255,
+};
+
+pub const CUSTOM_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 0,
+    b: 255,
+    a: 255,
+};
+
+// things start to visibly glow at about this temperature
+pub const DRAPER_POINT: f32 = 525.;
+
+// blackbody colour by temperature, alpha is how strongly it shows over the element's own
+pub const INCANDESCENCE: [(f32, ParticleColor); 5] = [
+    (
+        750.,
+        ParticleColor {
+            r: 190,
+            g: 20,
+            b: 0,
+            a: 110,
+        },
+    ),
+    (
+        1000.,
+        ParticleColor {
+            r: 255,
+            g: 100,
+            b: 10,
+            a: 190,
+        },
+    ),
+    (
+        1300.,
+        ParticleColor {
+            r: 255,
+            g: 190,
+            b: 60,
+            a: 235,
+        },
+    ),
+    (
+        1700.,
+        ParticleColor {
+            r: 255,
+            g: 240,
+            b: 190,
+            a: 255,
+        },
+    ),
+    (
+        2500.,
+        ParticleColor {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        },
+    ),
+];
+
+// light given off in the renderer's glow pass
+pub const FIRE_GLOW_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 120,
+    b: 30,
+    a: 255,
+};
+pub const LAVA_GLOW_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 80,
+    b: 10,
+    a: 255,
+};
+
+// render views, see `render`
+pub const HIGH_PRESSURE_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 60,
+    b: 40,
+    a: 255,
+};
+pub const LOW_PRESSURE_COLOR: ParticleColor = ParticleColor {
+    r: 40,
+    g: 120,
+    b: 255,
+    a: 255,
+};
+// snapshot grid lines, see `snapshot`
+pub const GRID_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 48,
+};
+// alpha is how much of the cell underneath shows through
+pub const MODIFIED_COLOR: ParticleColor = ParticleColor {
+    r: 255,
+    g: 230,
+    b: 0,
+    a: 96,
+};
+
 /*
             Variant::Empty => EMPTY_COLOR,
             Variant::Wall => WALL_COLOR,
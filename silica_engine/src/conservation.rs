@@ -0,0 +1,336 @@
+// random worlds run for a while must not create or destroy matter, apart from the
+// transformations listed with each scenario, and must not drift in temperature on their own
+use proptest::prelude::*;
+
+use crate::{
+    atmosphere::{Gas, PARTICLE_AMOUNT},
+    rng,
+    solution::{self, SALT_GRAIN},
+    variant::Variant,
+    variant_type::{VariantType, ACID},
+    world::World,
+};
+
+const WIDTH: i32 = 12;
+const TICKS: usize = 40;
+
+// empty is listed more than once so worlds have room to move
+const MOVING: [Variant; 12] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Sand,
+    Variant::Water,
+    Variant::Stone,
+    Variant::Oil,
+    Variant::Wall,
+    Variant::Glass,
+    Variant::HELM,
+    Variant::CARB,
+];
+const BRINE: [Variant; 5] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Salt,
+    Variant::Water,
+    Variant::Wall,
+];
+const MOLTEN: [Variant; 8] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Lava,
+    Variant::Stone,
+    Variant::Sand,
+    Variant::Glass,
+    Variant::Water,
+    Variant::Wall,
+];
+// oil and plant catch fire, fire dies down to smoke and smoke clears to nothing
+const BURNING: [Variant; 10] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Oil,
+    Variant::Plant,
+    Variant::Fire,
+    Variant::Sand,
+    Variant::Stone,
+    Variant::Glass,
+    Variant::Wall,
+];
+const FUEL: [Variant; 4] = [Variant::Oil, Variant::Plant, Variant::Fire, Variant::Smoke];
+// acid eats solids and powders other than glass, and is spent after ACID.strength of them
+const CORROSIVE: [Variant; 9] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Acid,
+    Variant::Sand,
+    Variant::Stone,
+    Variant::Water,
+    Variant::Glass,
+    Variant::Wall,
+];
+// plant grows into water, one cell of water for each of plant
+const GROWING: [Variant; 7] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Plant,
+    Variant::Water,
+    Variant::Sand,
+    Variant::Glass,
+    Variant::Wall,
+];
+// gas particles dissolve into the air of their cell and condense back out of it, a
+// particle being PARTICLE_AMOUNT of its gas. plants swap co2 in the air for oxygen
+const AIRY: [Variant; 8] = [
+    Variant::Empty,
+    Variant::Empty,
+    Variant::Empty,
+    Variant::OXGN,
+    Variant::NITR,
+    Variant::CO2,
+    Variant::Plant,
+    Variant::Wall,
+];
+// each group's total survives the transformations within it
+const PHASES: [&[Variant]; 3] = [
+    &[Variant::Lava, Variant::Stone],
+    &[Variant::Sand, Variant::Glass],
+    &[
+        Variant::Water,
+        Variant::SaltWater,
+        Variant::WTVP,
+        Variant::Ice,
+    ],
+];
+
+// cells as (element, temperature offset from its base), filled row by row. the
+// generator is seeded first so a failing case replays the same run
+fn build(palette: &[Variant], cells: &[(usize, i8)], gravity: u8, seed: u64) -> World {
+    rng::seed(seed);
+    let mut world = World::new(WIDTH, cells.len() as i32 / WIDTH);
+    world.set_gravity_angle(gravity as f32 * 90.);
+    for (i, (element, offset)) in cells.iter().enumerate() {
+        let (x, y) = (i as i32 % WIDTH, i as i32 / WIDTH);
+        world.set_particle(x, y, palette[*element % palette.len()]);
+        let idx = world.get_idx(x, y);
+        if world.particles[idx].get_variant() != Variant::Empty {
+            world.particles[idx].temperature += *offset as f32;
+        }
+    }
+    world
+}
+
+fn cells() -> impl Strategy<Value = Vec<(usize, i8)>> {
+    prop::collection::vec(
+        (0..64usize, -50..=50i8),
+        (WIDTH * 4) as usize..=(WIDTH * 12) as usize,
+    )
+    .prop_map(|mut cells| {
+        cells.truncate(cells.len() / WIDTH as usize * WIDTH as usize);
+        cells
+    })
+}
+
+fn count(world: &World, variants: &[Variant]) -> usize {
+    world
+        .particles
+        .iter()
+        .filter(|p| variants.contains(&p.get_variant()))
+        .count()
+}
+
+fn census(world: &World) -> Vec<usize> {
+    (0..=Variant::Custom as u8)
+        .map(|v| count(world, &[Variant::from_u8(v)]))
+        .collect()
+}
+
+// grains lying about plus those dissolved in water
+fn salt_grains(world: &World) -> f32 {
+    let dissolved: f32 = world
+        .particles
+        .iter()
+        .map(|p| solution::concentration(world, p))
+        .sum();
+    count(world, &[Variant::Salt]) as f32 + dissolved / SALT_GRAIN
+}
+
+// every particle only ever relaxes towards its element's base temperature, so the total
+// heat stays between the sums of the lower and upper ends of each particle's range
+fn heat_bounds(world: &World) -> (f32, f32) {
+    world
+        .particles
+        .iter()
+        .filter(|p| p.get_variant() != Variant::Empty)
+        .fold((0., 0.), |(low, high), p| {
+            let base = VariantType::from_variant(p.get_variant()).base_temperature;
+            (
+                low + p.temperature.min(base),
+                high + p.temperature.max(base),
+            )
+        })
+}
+
+// particles of the gas plus what is dissolved in the air
+fn gas_total(world: &World, gas: Gas) -> f32 {
+    let dissolved: f32 = world.environment.iter().map(|env| env.gas.get(gas)).sum();
+    count(world, &[gas.variant()]) as f32 * PARTICLE_AMOUNT + dissolved
+}
+
+fn heat(world: &World) -> f32 {
+    world.particles.iter().map(|p| p.temperature).sum()
+}
+
+fn run(world: &mut World) {
+    for _ in 0..TICKS {
+        world.tick();
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn movement_conserves_every_element(cells in cells(), gravity in 0..4u8, seed in any::<u64>()) {
+        let mut world = build(&MOVING, &cells, gravity, seed);
+        let before = census(&world);
+        let (low, high) = heat_bounds(&world);
+        run(&mut world);
+
+        prop_assert_eq!(census(&world), before);
+        let heat = heat(&world);
+        let slack = 1e-3 * cells.len() as f32;
+        prop_assert!(heat >= low - slack && heat <= high + slack, "{} outside {}..{}", heat, low, high);
+    }
+
+    #[test]
+    fn salt_dissolves_without_losing_grains(cells in cells(), seed in any::<u64>()) {
+        let cells: Vec<(usize, i8)> = cells.into_iter().map(|(e, _)| (e, 0)).collect();
+        let mut world = build(&BRINE, &cells, 0, seed);
+        let water = count(&world, &[Variant::Water, Variant::SaltWater]);
+        let salt = salt_grains(&world);
+        run(&mut world);
+
+        prop_assert_eq!(count(&world, &[Variant::Water, Variant::SaltWater]), water);
+        prop_assert!((salt_grains(&world) - salt).abs() < 1e-3, "{} grains became {}", salt, salt_grains(&world));
+    }
+
+    #[test]
+    fn boiling_brine_keeps_its_salt(cells in cells(), seed in any::<u64>()) {
+        // around 200 degrees give or take 50, so most of the water boils off in the run
+        let mut world = build(&BRINE, &cells, 0, seed);
+        for particle in world.particles.iter_mut().filter(|p| p.get_variant() != Variant::Empty) {
+            particle.temperature += 180.;
+        }
+        let water = count(&world, &[Variant::Water, Variant::SaltWater]);
+        let salt = salt_grains(&world);
+        run(&mut world);
+
+        prop_assert_eq!(count(&world, &[Variant::Water, Variant::SaltWater, Variant::WTVP]), water);
+        prop_assert!((salt_grains(&world) - salt).abs() < 1e-3, "{} grains became {}", salt, salt_grains(&world));
+    }
+
+    #[test]
+    fn phase_changes_only_transform(cells in cells(), gravity in 0..4u8, seed in any::<u64>()) {
+        let mut world = build(&MOLTEN, &cells, gravity, seed);
+        let before: Vec<usize> = PHASES.iter().map(|group| count(&world, group)).collect();
+        let walls = count(&world, &[Variant::Wall]);
+        run(&mut world);
+
+        let after: Vec<usize> = PHASES.iter().map(|group| count(&world, group)).collect();
+        prop_assert_eq!(after, before);
+        prop_assert_eq!(count(&world, &[Variant::Wall]), walls);
+    }
+
+    #[test]
+    fn burning_only_uses_up_fuel(cells in cells(), gravity in 0..4u8, seed in any::<u64>()) {
+        let mut world = build(&BURNING, &cells, gravity, seed);
+        let before = census(&world);
+        let fuel = count(&world, &FUEL);
+        run(&mut world);
+
+        let after = census(&world);
+        for variant in [Variant::Sand, Variant::Stone, Variant::Glass, Variant::Wall] {
+            prop_assert_eq!(after[variant as usize], before[variant as usize], "{:?}", variant);
+        }
+        for variant in [Variant::Oil, Variant::Plant] {
+            prop_assert!(after[variant as usize] <= before[variant as usize], "{:?}", variant);
+        }
+        prop_assert!(count(&world, &FUEL) <= fuel);
+    }
+
+    #[test]
+    fn acid_only_eats_what_it_can(cells in cells(), gravity in 0..4u8, seed in any::<u64>()) {
+        let mut world = build(&CORROSIVE, &cells, gravity, seed);
+        let before = census(&world);
+        run(&mut world);
+
+        let after = census(&world);
+        for variant in [Variant::Water, Variant::Glass, Variant::Wall] {
+            prop_assert_eq!(after[variant as usize], before[variant as usize], "{:?}", variant);
+        }
+        let acid = Variant::Acid as usize;
+        prop_assert!(after[acid] <= before[acid]);
+        let eaten: usize = [Variant::Sand, Variant::Stone]
+            .iter()
+            .map(|v| before[*v as usize] - after[*v as usize])
+            .sum();
+        prop_assert!(eaten <= before[acid] * ACID.strength as usize, "{} eaten by {} acid", eaten, before[acid]);
+    }
+
+    #[test]
+    fn plant_grows_from_water(cells in cells(), gravity in 0..4u8, seed in any::<u64>()) {
+        let mut world = build(&GROWING, &cells, gravity, seed);
+        let before = census(&world);
+        let (plant, water) = (Variant::Plant as usize, Variant::Water as usize);
+        run(&mut world);
+
+        let after = census(&world);
+        prop_assert!(after[plant] >= before[plant]);
+        prop_assert_eq!(after[plant] + after[water], before[plant] + before[water]);
+        for variant in [Variant::Sand, Variant::Glass, Variant::Wall] {
+            prop_assert_eq!(after[variant as usize], before[variant as usize], "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn gas_moves_between_particles_and_air(cells in cells(), gravity in 0..4u8, seed in any::<u64>()) {
+        let mut world = build(&AIRY, &cells, gravity, seed);
+        world.set_atmosphere(true);
+        let total = |world: &World| Gas::ALL.iter().map(|gas| gas_total(world, *gas)).sum::<f32>();
+        let (all, nitrogen) = (total(&world), gas_total(&world, Gas::N2));
+        let walls = count(&world, &[Variant::Wall]);
+        run(&mut world);
+
+        prop_assert!((total(&world) - all).abs() < 1e-2, "{} became {}", all, total(&world));
+        let n2 = gas_total(&world, Gas::N2);
+        prop_assert!((n2 - nitrogen).abs() < 1e-2, "{} nitrogen became {}", nitrogen, n2);
+        prop_assert_eq!(count(&world, &[Variant::Wall]), walls);
+    }
+}
+
+// one oxygen and one hydrogen make one water, so each gas plus water stays constant
+#[test]
+fn oxygen_and_hydrogen_make_water() {
+    let mut world = World::new(8, 8);
+    for y in 0..8 {
+        for x in 0..8 {
+            let variant = match (x + y) % 2 {
+                0 => Variant::OXGN,
+                _ => Variant::HYGN,
+            };
+            world.set_particle(x, y, variant);
+        }
+    }
+    let oxygen = count(&world, &[Variant::OXGN]);
+    let hydrogen = count(&world, &[Variant::HYGN]);
+    run(&mut world);
+
+    let water = count(&world, &[Variant::Water]);
+    assert!(water > 0);
+    assert_eq!(count(&world, &[Variant::OXGN]) + water, oxygen);
+    assert_eq!(count(&world, &[Variant::HYGN]) + water, hydrogen);
+}
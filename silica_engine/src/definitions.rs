@@ -0,0 +1,429 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ron::extensions::Extensions;
+use serde::Deserialize;
+
+use crate::{
+    elements::builtin_by_code,
+    group::{ElementInfo, ElementManager},
+    metadata::{self, Field, FieldKind},
+    variant::Variant,
+    variant_type::{flag_from_name, ParticleColor, VariantProperty, VariantType, CUSTOM},
+    world::World,
+};
+
+// one element of a definitions file. a code naming a built in element ("WATR") changes
+// that element, any other code adds a runtime one. left out fields keep the built in
+// value, or the CUSTOM template's for new elements
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ElementDef {
+    pub name: String,
+    // defaults to the name in upper case
+    pub code: Option<String>,
+    // [r, g, b] or [r, g, b, a]
+    pub color: Option<Vec<u8>>,
+    pub weight: Option<u8>,
+    pub strength: Option<u8>,
+    pub property: Option<String>,
+    // replaces the base element's flags when given
+    pub flags: Option<Vec<String>>,
+    pub base_temperature: Option<f32>,
+    // state each particle keeps, [name, kind] pairs in order, e.g. [["charge", "u8"]].
+    // only for runtime elements
+    pub fields: Option<Vec<(String, String)>>,
+    // how pickers show it
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub hidden: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupDef {
+    pub name: String,
+    // short codes, or names of runtime elements
+    pub elements: Vec<String>,
+}
+
+// elements and groups as read from a file:
+//
+//     [[elements]]
+//     name = "Water"
+//     code = "WATR"
+//     color = [40, 90, 255]
+//
+//     [[elements]]
+//     name = "Mud"
+//     property = "liquid"
+//     weight = 80
+//
+//     [[groups]]
+//     name = "Liquids"
+//     elements = ["WATR", "MUD"]
+//
+// or the same in ron, `(elements: [(name: "Water", code: "WATR")], groups: [])`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Definitions {
+    #[serde(default)]
+    pub elements: Vec<ElementDef>,
+    #[serde(default)]
+    pub groups: Vec<GroupDef>,
+}
+
+impl ElementDef {
+    pub fn code(&self) -> String {
+        self.code
+            .clone()
+            .unwrap_or_else(|| self.name.to_uppercase())
+    }
+
+    // the built in element this changes, if any
+    pub fn builtin(&self) -> Option<Variant> {
+        builtin_by_code(&self.code())
+    }
+
+    fn variant_type(&self, mut variant_type: VariantType) -> Result<VariantType, String> {
+        if let Some(color) = &self.color {
+            variant_type.color = match color[..] {
+                [r, g, b] => ParticleColor::from_rgba((r, g, b, 255)),
+                [r, g, b, a] => ParticleColor::from_rgba((r, g, b, a)),
+                _ => return Err(format!("color needs 3 or 4 channels, not {}", color.len())),
+            };
+        }
+        if let Some(weight) = self.weight {
+            variant_type.weight = weight;
+        }
+        if let Some(strength) = self.strength {
+            variant_type.strength = strength;
+        }
+        if let Some(property) = &self.property {
+            variant_type.variant_property = VariantProperty::from_name(property)?;
+        }
+        if let Some(flags) = &self.flags {
+            variant_type.flags = 0;
+            for flag in flags {
+                variant_type.flags |= flag_from_name(flag)?;
+            }
+        }
+        if let Some(temperature) = self.base_temperature {
+            variant_type.base_temperature = temperature;
+        }
+        Ok(variant_type)
+    }
+
+    fn fields(&self) -> Result<Option<Vec<Field>>, String> {
+        let Some(fields) = &self.fields else {
+            return Ok(None);
+        };
+        if self.builtin().is_some() {
+            return Err("built in elements can't declare fields".to_string());
+        }
+        let fields = fields
+            .iter()
+            .map(|(name, kind)| Ok(Field::named(name, FieldKind::from_name(kind)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        metadata::check_fields(&fields)?;
+        Ok(Some(fields))
+    }
+
+    fn info(&self, mut info: ElementInfo) -> ElementInfo {
+        info.name = self.name.clone();
+        if let Some(description) = &self.description {
+            info.description = description.clone();
+        }
+        if self.icon.is_some() {
+            info.icon = self.icon.clone();
+        }
+        if let Some(hidden) = self.hidden {
+            info.hidden = hidden;
+        }
+        info
+    }
+
+    fn names(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.code().eq_ignore_ascii_case(name)
+    }
+}
+
+impl Definitions {
+    pub fn from_toml(source: &str) -> Result<Definitions, String> {
+        toml::from_str(source).map_err(|e| e.to_string())
+    }
+
+    // optional fields can be written without Some(..)
+    pub fn from_ron(source: &str) -> Result<Definitions, String> {
+        ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(source)
+            .map_err(|e| e.to_string())
+    }
+
+    // the format is picked by extension, .ron or .toml
+    pub fn load(path: &Path) -> Result<Definitions, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let definitions = match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => Definitions::from_ron(&source),
+            Some("toml") => Definitions::from_toml(&source),
+            _ => return Err(format!("{}: expected a .toml or .ron file", path.display())),
+        };
+        definitions.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // everything is checked before the world is touched, so a bad file changes nothing.
+    // returns the types of the defined elements, in file order
+    pub fn apply(
+        &self,
+        world: &mut World,
+        groups: &mut ElementManager,
+    ) -> Result<Vec<VariantType>, String> {
+        let mut types = vec![];
+        let mut fields = vec![];
+        for def in &self.elements {
+            let base = def
+                .builtin()
+                .map_or(CUSTOM, |variant| *VariantType::from_variant(variant));
+            let variant_type = def
+                .variant_type(base)
+                .map_err(|e| format!("{}: {}", def.name, e))?;
+            types.push(variant_type);
+            fields.push(def.fields().map_err(|e| format!("{}: {}", def.name, e))?);
+        }
+        for group in &self.groups {
+            for name in &group.elements {
+                if world.find_element(name).is_none()
+                    && !self.elements.iter().any(|d| d.names(name))
+                {
+                    return Err(format!("group {}: unknown element {:?}", group.name, name));
+                }
+            }
+        }
+
+        for ((def, variant_type), fields) in self.elements.iter().zip(types.iter_mut()).zip(fields)
+        {
+            if def.builtin().is_some() {
+                world.override_element(*variant_type);
+                continue;
+            }
+            // a file can tune a scripted or plugin element without losing its behaviour
+            let behaviour = world
+                .elements
+                .find(&def.name)
+                .and_then(|e| e.behaviour.clone());
+            *variant_type =
+                world.register_element(&def.name, &def.code(), *variant_type, behaviour);
+            if let Some(fields) = fields {
+                // already checked, this can't fail
+                world.register_fields(*variant_type, fields)?;
+            }
+        }
+        // entries already in the catalogue pick up the new look
+        for (def, variant_type) in self.elements.iter().zip(&types) {
+            groups.refresh(&def.info(ElementInfo::of(&world.elements, *variant_type)));
+        }
+        for group in &self.groups {
+            let elements = group
+                .elements
+                .iter()
+                .filter_map(|name| world.find_element(name))
+                .map(|variant_type| {
+                    let info = ElementInfo::of(&world.elements, variant_type);
+                    match self.elements.iter().find(|d| d.code() == info.code) {
+                        Some(def) => def.info(info),
+                        None => info,
+                    }
+                })
+                .collect();
+            groups.register_group(&group.name, elements);
+        }
+        Ok(types)
+    }
+}
+
+// reloads a definitions file whenever it changes on disk, poll it once a frame
+pub struct DefinitionsWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // built in elements the file changed, reset once it stops mentioning them
+    overridden: Vec<Variant>,
+}
+
+impl DefinitionsWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> DefinitionsWatcher {
+        DefinitionsWatcher {
+            path: path.into(),
+            modified: None,
+            overridden: vec![],
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Some with the outcome when the file was (re)loaded, None if it hasn't changed.
+    // a file that fails to load isn't retried until it is saved again
+    pub fn poll(
+        &mut self,
+        world: &mut World,
+        groups: &mut ElementManager,
+    ) -> Option<Result<Vec<VariantType>, String>> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.reload(world, groups))
+    }
+
+    pub fn reload(
+        &mut self,
+        world: &mut World,
+        groups: &mut ElementManager,
+    ) -> Result<Vec<VariantType>, String> {
+        let definitions = Definitions::load(&self.path)?;
+        let types = definitions.apply(world, groups)?;
+
+        let overridden: Vec<Variant> = definitions
+            .elements
+            .iter()
+            .filter_map(ElementDef::builtin)
+            .collect();
+        for variant in self.overridden.iter().filter(|v| !overridden.contains(v)) {
+            world.reset_element(*variant);
+            groups.refresh(&ElementInfo::builtin(*variant));
+        }
+        self.overridden = overridden;
+        Ok(types)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        metadata::Value,
+        particle::{particle_to_color, Particle},
+        variant_type::{FLAG_BURNS, WATER},
+    };
+
+    const ELEMENTS: &str = r#"
+        [[elements]]
+        name = "Water"
+        code = "WATR"
+        color = [1, 2, 3]
+        weight = 40
+
+        [[elements]]
+        name = "Mud"
+        property = "liquid"
+        flags = ["burns"]
+        base_temperature = 30.0
+        fields = [["wetness", "f32"]]
+        description = "Wet dirt"
+
+        [[groups]]
+        name = "Wet"
+        elements = ["WATR", "mud"]
+    "#;
+
+    #[test]
+    fn test_toml_and_ron() {
+        let mut world = World::new(4, 4);
+        let mut groups = ElementManager::new();
+        world.set_particle(0, 0, Variant::Water);
+        let types = Definitions::from_toml(ELEMENTS)
+            .unwrap()
+            .apply(&mut world, &mut groups)
+            .unwrap();
+
+        // the override keeps water's behaviour and reaches water already placed
+        assert_eq!(types[0].source_variant, Variant::Water);
+        assert_eq!(world.get_particle(0, 0).variant_type.weight, 40);
+        world.set_particle(1, 0, Variant::Water);
+        assert_eq!(world.get_particle(1, 0).variant_type.color.b, 3);
+        // brine with nothing left in it looks like the overridden water
+        let mut brine = Particle::new(*VariantType::from_variant(Variant::SaltWater), 0, 0);
+        world
+            .set_field(&mut brine, "concentration", Value::F32(0.))
+            .unwrap();
+        assert_eq!(particle_to_color(&world, brine).to_rgb8(), (1, 2, 3));
+
+        let mud = world.find_element("MUD").unwrap();
+        assert_eq!(mud.variant_property, VariantProperty::Liquid);
+        assert!(mud.has_flag(FLAG_BURNS));
+        let placed = Particle::new(mud, 0, 0);
+        assert_eq!(world.get_field(&placed, "wetness"), Some(Value::F32(0.)));
+        assert_eq!(groups.get_group("Wet"), vec![types[0], mud]);
+        let entries = groups.get_entries("Wet", false);
+        assert_eq!(entries[1].description, "Wet dirt");
+        assert_eq!(entries[0].color, types[0].color);
+
+        // ron reads the same, and a second load replaces the group
+        let ron = r#"(
+            elements: [(name: "Mud", weight: 90)],
+            groups: [(name: "Wet", elements: ["MUD"])],
+        )"#;
+        Definitions::from_ron(ron)
+            .unwrap()
+            .apply(&mut world, &mut groups)
+            .unwrap();
+        let mud = world.find_element("Mud").unwrap();
+        assert_eq!((mud.weight, mud.custom), (90, 1));
+        // left out fields are kept
+        assert_eq!(world.fields(&Particle::new(mud, 0, 0)).len(), 1);
+        assert_eq!(groups.get_group("Wet"), vec![mud]);
+
+        // nothing is applied from a file with a mistake in it
+        let bad = "[[groups]]\nname = \"Dry\"\nelements = [\"DUST\"]";
+        let err = Definitions::from_toml(bad)
+            .unwrap()
+            .apply(&mut world, &mut groups);
+        assert!(err.unwrap_err().contains("DUST"));
+        assert!(groups.get_group("Dry").is_empty());
+        assert!(
+            Definitions::from_toml("[[elements]]\nname = \"X\"\nproperty = \"plasma\"")
+                .unwrap()
+                .apply(&mut world, &mut groups)
+                .is_err()
+        );
+        assert!(Definitions::from_toml(
+            "[[elements]]\nname = \"Sand\"\ncode = \"SAND\"\nfields = [[\"grit\", \"u8\"]]"
+        )
+        .unwrap()
+        .apply(&mut world, &mut groups)
+        .is_err());
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let path = std::env::temp_dir().join(format!("silica_defs_{}.toml", std::process::id()));
+        fs::write(&path, ELEMENTS).unwrap();
+        let mut world = World::new(4, 4);
+        let mut groups = ElementManager::builtin();
+        let mut watcher = DefinitionsWatcher::new(&path);
+
+        assert!(watcher.poll(&mut world, &mut groups).unwrap().is_ok());
+        assert!(watcher.poll(&mut world, &mut groups).is_none());
+        assert_eq!(world.find_element("WATR").unwrap().weight, 40);
+        assert_eq!(groups.find("WATR").unwrap().color.b, 3);
+
+        // dropping water from the file puts it back the way it was compiled
+        fs::write(&path, "[[elements]]\nname = \"Mud\"\nweight = 10\n").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        assert!(watcher.poll(&mut world, &mut groups).unwrap().is_ok());
+        assert_eq!(world.find_element("WATR"), Some(WATER));
+        assert_eq!(groups.find("WATR").unwrap().color, WATER.color);
+        assert_eq!(world.find_element("MUD").unwrap().weight, 10);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    api::API,
+    events::Event,
+    metadata::Value,
+    particle::Particle,
+    variant::Variant,
+    variant_type::{FIRE, FLAG_BURNS, FLAG_CONDUCTS},
+    world::World,
+};
+
+// a spark is live for one tick at SPARK_LIFE, then counts down to 0 while the
+// conductor is refractory and can't be sparked again
+pub const SPARK_LIFE: u8 = 4;
+pub const SPARK_HEAT: f32 = 15.;
+
+const NBRS: [(i32, i32); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+
+// switches keep their on/off state in the "on" metadata field
+pub fn switch_on(world: &World, particle: &Particle) -> bool {
+    world.get_u8(particle, "on") != 0
+}
+
+// the engine keeps a "spark" field for every conductor
+pub fn spark_of(world: &World, particle: &Particle) -> u8 {
+    world.get_u8(particle, "spark")
+}
+
+pub fn is_conductor(world: &World, particle: &Particle) -> bool {
+    particle.variant_type.has_flag(FLAG_CONDUCTS)
+        && (particle.get_variant() != Variant::SWCH || switch_on(world, particle))
+}
+
+// start a spark at the given offset if it holds an idle conductor,
+// cells that already changed this tick wait for the next one
+pub fn spark(api: &mut API, dx: i32, dy: i32) -> bool {
+    let mut nbr = api.get(dx, dy);
+    if !is_conductor(api.world, &nbr) || spark_of(api.world, &nbr) != 0 {
+        return false;
+    }
+    if nbr.clock == api.world.generation.wrapping_add(1) {
+        return false;
+    }
+
+    api.set_field(&mut nbr, "spark", Value::U8(SPARK_LIFE))
+        .unwrap();
+    api.set(dx, dy, nbr);
+    true
+}
+
+// advance the spark on a conductor, a live spark spreads to idle neighbours,
+// heats the conductor and ignites anything burnable next to it
+pub fn conduct(mut particle: Particle, api: &mut API) -> bool {
+    let left = spark_of(api.world, &particle);
+    if left == 0 {
+        return false;
+    }
+
+    // the cell moved away before its update ran (flowing salt water)
+    if api.get(0, 0).get_variant() != particle.get_variant() {
+        return false;
+    }
+
+    if left == SPARK_LIFE {
+        for (dx, dy) in NBRS {
+            if spark(api, dx, dy) {
+                continue;
+            }
+
+            let nbr = api.get(dx, dy);
+            if nbr.variant_type.has_flag(FLAG_BURNS) && nbr.get_variant() != Variant::Fire {
+                api.emit(Event::Ignition {
+                    x: api.x + dx,
+                    y: api.y + dy,
+                    variant: nbr.get_variant(),
+                });
+                api.set(
+                    dx,
+                    dy,
+                    Particle {
+                        variant_type: FIRE,
+                        ..nbr
+                    },
+                );
+            }
+        }
+        particle.add_heat(SPARK_HEAT);
+    }
+
+    api.set_field(&mut particle, "spark", Value::U8(left - 1))
+        .unwrap();
+    api.set(0, 0, particle);
+    true
+}
+
+// batteries spark every idle conductor they touch, so a wire pulses once per refractory period
+pub fn power(api: &mut API) -> bool {
+    let mut sparked = false;
+    for (dx, dy) in NBRS {
+        sparked |= spark(api, dx, dy);
+    }
+    sparked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    fn wire(world: &mut World, switch_on: bool) {
+        world.set_particle(0, 1, Variant::BTRY);
+        for x in 1..8 {
+            world.set_particle(x, 1, Variant::IRON);
+        }
+        world.set_particle(4, 1, Variant::SWCH);
+        if switch_on {
+            world.toggle_switch(4, 1);
+        }
+    }
+
+    fn sparked_at(world: &mut World, x: i32, ticks: usize) -> bool {
+        (0..ticks).any(|_| {
+            world.tick();
+            spark_of(world, &world.get_particle(x, 1)) != 0
+        })
+    }
+
+    #[test]
+    fn test_spark_travels_along_wire() {
+        let mut world = World::new(10, 4);
+        wire(&mut world, true);
+        assert!(sparked_at(&mut world, 7, 12));
+
+        // heats up once the spark goes live
+        world.tick();
+        assert!(world.get_particle(7, 1).temperature >= SPARK_HEAT);
+    }
+
+    #[test]
+    fn test_open_switch_blocks_spark() {
+        let mut world = World::new(10, 4);
+        wire(&mut world, false);
+        assert!(sparked_at(&mut world, 3, 12));
+        assert!(!sparked_at(&mut world, 5, 12));
+    }
+
+    #[test]
+    fn test_refractory_period() {
+        let mut world = World::new(10, 4);
+        wire(&mut world, true);
+        let mut states = vec![];
+        for _ in 0..8 {
+            world.tick();
+            states.push(spark_of(&world, &world.get_particle(1, 1)));
+        }
+        // sparked on the first tick, then has to cool down before the next pulse
+        assert_eq!(states, vec![4, 3, 2, 1, 0, 4, 3, 2]);
+    }
+}
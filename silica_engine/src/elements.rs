@@ -0,0 +1,253 @@
+use std::sync::Arc;
+
+use crate::{
+    api::API,
+    metadata::Field,
+    particle::Particle,
+    variant::Variant,
+    variant_type::{VariantType, VARIANT_COUNT},
+};
+
+// how a runtime element behaves, called like the update functions in `variant`
+pub trait Behaviour: Send + Sync {
+    fn update(&self, particle: Particle, api: &mut API) -> bool;
+}
+
+#[derive(Clone)]
+pub struct CustomElement {
+    pub name: String,
+    pub code: String,
+    pub variant_type: VariantType,
+    pub behaviour: Option<Arc<dyn Behaviour>>,
+    // state each particle keeps in the world's `StateBuffer`, see `metadata`
+    pub fields: Vec<Field>,
+}
+
+// elements registered at runtime, particles find theirs through variant_type.custom.
+// cheap to clone, behaviours hold on to a copy while they run
+#[derive(Clone, Default)]
+pub struct Elements {
+    elements: Arc<Vec<CustomElement>>,
+    // replacement types for built in elements, indexed by variant
+    builtins: Arc<Vec<Option<VariantType>>>,
+}
+
+impl Elements {
+    // registering a name again replaces the element but keeps its id and fields
+    pub fn register(
+        &mut self,
+        name: &str,
+        code: &str,
+        mut variant_type: VariantType,
+        behaviour: Option<Arc<dyn Behaviour>>,
+    ) -> VariantType {
+        let elements = Arc::make_mut(&mut self.elements);
+        let idx = elements
+            .iter()
+            .position(|e| e.name == name)
+            .unwrap_or(elements.len());
+
+        variant_type.source_variant = Variant::Custom;
+        variant_type.custom = idx as u16 + 1;
+        let fields = elements
+            .get(idx)
+            .map_or_else(Vec::new, |e| e.fields.clone());
+        let element = CustomElement {
+            name: name.to_string(),
+            code: code.to_string(),
+            variant_type,
+            behaviour,
+            fields,
+        };
+        if idx == elements.len() {
+            elements.push(element);
+        } else {
+            elements[idx] = element;
+        }
+        variant_type
+    }
+
+    // returns false if there's no element with that id
+    pub fn set_fields(&mut self, id: u16, fields: Vec<Field>) -> bool {
+        let Some(idx) = usize::from(id).checked_sub(1) else {
+            return false;
+        };
+        match Arc::make_mut(&mut self.elements).get_mut(idx) {
+            Some(element) => {
+                element.fields = fields;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, id: u16) -> Option<&CustomElement> {
+        self.elements.get(usize::from(id).checked_sub(1)?)
+    }
+
+    // by name or short code, ignoring case
+    pub fn find(&self, name: &str) -> Option<&CustomElement> {
+        self.elements
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name) || e.code.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CustomElement> {
+        self.elements.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    // replace the type of a built in element, it keeps its behaviour
+    pub fn override_builtin(&mut self, variant_type: VariantType) {
+        let builtins = Arc::make_mut(&mut self.builtins);
+        builtins.resize(VARIANT_COUNT, None);
+        builtins[variant_type.source_variant as usize] = Some(VariantType {
+            custom: 0,
+            ..variant_type
+        });
+    }
+
+    pub fn reset_builtin(&mut self, variant: Variant) {
+        if self.overridden(variant).is_some() {
+            Arc::make_mut(&mut self.builtins)[variant as usize] = None;
+        }
+    }
+
+    pub fn overridden(&self, variant: Variant) -> Option<VariantType> {
+        self.builtins.get(variant as usize).copied().flatten()
+    }
+
+    // the type particles of a built in element are placed with
+    pub fn builtin(&self, variant: Variant) -> VariantType {
+        self.overridden(variant)
+            .unwrap_or(*VariantType::from_variant(variant))
+    }
+
+    // built in elements by short code ("WATR"), then runtime ones by name or code
+    pub fn lookup(&self, name: &str) -> Option<VariantType> {
+        match builtin_by_code(name) {
+            Some(variant) => Some(self.builtin(variant)),
+            None => self.find(name).map(|e| e.variant_type),
+        }
+    }
+
+    // the short code for any particle, built in or not
+    pub fn code_of(&self, particle: &Particle) -> String {
+        match particle.get_variant() {
+            Variant::Custom => self.get(particle.variant_type.custom).map_or_else(
+                || Variant::Custom.get_name().to_string(),
+                |e| e.code.clone(),
+            ),
+            variant => variant.get_name().to_string(),
+        }
+    }
+}
+
+pub fn builtin_by_code(code: &str) -> Option<Variant> {
+    (0..VARIANT_COUNT as u8)
+        .map(Variant::from_u8)
+        .filter(|v| *v != Variant::Custom)
+        .find(|v| v.get_name().eq_ignore_ascii_case(code))
+}
+
+pub(crate) fn update(particle: Particle, mut api: API) -> bool {
+    let behaviour = api
+        .world
+        .elements
+        .get(particle.variant_type.custom)
+        .and_then(|e| e.behaviour.clone());
+    match behaviour {
+        Some(behaviour) => behaviour.update(particle, &mut api),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        variant_type::{VariantProperty, CUSTOM},
+        world::World,
+    };
+
+    // turns everything next to it into copies of itself
+    struct Spread;
+
+    impl Behaviour for Spread {
+        fn update(&self, particle: Particle, api: &mut API) -> bool {
+            let (dx, dy) = api.rand_vec();
+            if api.get(dx, dy).get_variant() == Variant::Sand {
+                api.set(dx, dy, particle);
+                return true;
+            }
+            false
+        }
+    }
+
+    #[test]
+    fn test_register_and_lookup() {
+        let mut world = World::new(4, 4);
+        let blob = world.register_element(
+            "Blob",
+            "BLOB",
+            VariantType {
+                variant_property: VariantProperty::Solid,
+                ..CUSTOM
+            },
+            Some(Arc::new(Spread)),
+        );
+        assert_eq!(blob.custom, 1);
+        assert_eq!(world.find_element("blob"), Some(blob));
+        assert_eq!(
+            world.find_element("WATR").unwrap().source_variant,
+            Variant::Water
+        );
+        assert_eq!(world.find_element("nope"), None);
+
+        // re-registering keeps the id and updates particles already placed
+        world.set_element(0, 0, blob);
+        let heavier = world.register_element(
+            "Blob",
+            "BLOB",
+            VariantType {
+                weight: 200,
+                ..blob
+            },
+            None,
+        );
+        assert_eq!(heavier.custom, 1);
+        assert_eq!(world.get_particle(0, 0).variant_type.weight, 200);
+    }
+
+    #[test]
+    fn test_custom_behaviour_runs() {
+        let mut world = World::new(3, 3);
+        let blob = world.register_element(
+            "Blob",
+            "BLOB",
+            VariantType {
+                variant_property: VariantProperty::Solid,
+                ..CUSTOM
+            },
+            Some(Arc::new(Spread)),
+        );
+        for x in 0..3 {
+            for y in 0..3 {
+                world.set_particle(x, y, Variant::Sand);
+            }
+        }
+        world.set_element(1, 1, blob);
+        for _ in 0..200 {
+            world.tick();
+        }
+        assert_eq!(world.get_particle(1, 1).variant_type, blob);
+        assert_eq!(world.elements.code_of(&world.get_particle(0, 0)), "BLOB");
+    }
+}
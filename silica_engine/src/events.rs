@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{variant::Variant, world::World};
+
+// things that happened during a tick, coordinates are world cells.
+// the world clears them at the start of each tick, drain them after it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    // two elements combined, `product` was placed at (x, y)
+    Reaction {
+        x: i32,
+        y: i32,
+        reactants: (Variant, Variant),
+        product: Variant,
+    },
+    // melting, freezing, boiling and condensing. gas condensing out of the air comes
+    // `from` Empty, salt falling out of brine `from` SaltWater
+    PhaseChange {
+        x: i32,
+        y: i32,
+        from: Variant,
+        to: Variant,
+    },
+    // something caught fire
+    Ignition {
+        x: i32,
+        y: i32,
+        variant: Variant,
+    },
+    // an explosive element went off, everything within `radius` cells is on fire
+    Explosion {
+        x: i32,
+        y: i32,
+        variant: Variant,
+        radius: i32,
+    },
+    // a particle reached the end of its lifetime and turned into `product`
+    Decay {
+        x: i32,
+        y: i32,
+        variant: Variant,
+        product: Variant,
+    },
+    // a particle moved past the edge of a world with open edges and was destroyed
+    LeftWorld {
+        x: i32,
+        y: i32,
+        variant: Variant,
+    },
+    // the number of `variant` particles went above (or fell below) a watched threshold
+    CountCrossed {
+        variant: Variant,
+        threshold: usize,
+        count: usize,
+        rising: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CountWatch {
+    pub variant: Variant,
+    pub threshold: usize,
+    pub above: bool,
+}
+
+fn count(world: &World, variant: Variant) -> usize {
+    world
+        .particles
+        .iter()
+        .filter(|p| p.get_variant() == variant)
+        .count()
+}
+
+impl CountWatch {
+    pub fn new(world: &World, variant: Variant, threshold: usize) -> CountWatch {
+        CountWatch {
+            variant,
+            threshold,
+            above: count(world, variant) >= threshold,
+        }
+    }
+}
+
+// emit CountCrossed for every watch whose side of the threshold changed this tick
+pub(crate) fn check_counts(world: &mut World) {
+    for i in 0..world.watches.len() {
+        let watch = world.watches[i];
+        let count = count(world, watch.variant);
+        let above = count >= watch.threshold;
+        if above == watch.above {
+            continue;
+        }
+
+        world.watches[i].above = above;
+        world.events.push(Event::CountCrossed {
+            variant: watch.variant,
+            threshold: watch.threshold,
+            count,
+            rising: above,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        solution::{self, SALT_GRAIN, SATURATION},
+        variant::EXPLOSION_RADIUS,
+    };
+
+    #[test]
+    fn test_particle_leaves_world() {
+        // closed edges read as wall, sand piles up on the floor
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 2, Variant::Sand);
+        world.tick();
+        assert_eq!(world.drain_events().count(), 0);
+        assert_eq!(world.get_particle(1, 2).get_variant(), Variant::Sand);
+
+        // open ones let it fall through
+        world.set_open_edges(true);
+        world.tick();
+        let events: Vec<Event> = world.drain_events().collect();
+        assert!(matches!(
+            events[..],
+            [Event::LeftWorld {
+                y: 3,
+                variant: Variant::Sand,
+                ..
+            }]
+        ));
+        assert_eq!(world.get_particle_count(), 0);
+        assert_eq!(world.drain_events().count(), 0);
+    }
+
+    #[test]
+    fn test_explosion() {
+        let mut world = World::new(7, 7);
+        world.set_particle(3, 3, Variant::HYGN);
+        world.set_particle(3, 4, Variant::Fire);
+        world.set_particle(0, 0, Variant::Wall);
+        world.tick();
+
+        let events: Vec<Event> = world.drain_events().collect();
+        assert!(events.contains(&Event::Explosion {
+            x: 3,
+            y: 3,
+            variant: Variant::HYGN,
+            radius: EXPLOSION_RADIUS,
+        }));
+        assert_eq!(world.get_particle(1, 3).get_variant(), Variant::Fire);
+        assert_eq!(world.get_particle(0, 0).get_variant(), Variant::Wall);
+    }
+
+    #[test]
+    fn test_decay_and_precipitation() {
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 1, Variant::Fire);
+        let mut decayed = false;
+        for _ in 0..200 {
+            world.tick();
+            decayed |= world.drain_events().any(|e| {
+                matches!(
+                    e,
+                    Event::Decay {
+                        variant: Variant::Fire,
+                        product: Variant::Smoke,
+                        ..
+                    }
+                )
+            });
+        }
+        assert!(decayed);
+
+        // more salt than the brine can hold falls out
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 2, Variant::SaltWater);
+        let idx = world.get_idx(1, 2);
+        let brine = world.particles[idx];
+        world.particles[idx] =
+            solution::with_concentration(&mut world, brine, SATURATION + SALT_GRAIN);
+        let mut precipitated = false;
+        for _ in 0..20 {
+            world.tick();
+            precipitated |= world.drain_events().any(|e| {
+                matches!(
+                    e,
+                    Event::PhaseChange {
+                        from: Variant::SaltWater,
+                        to: Variant::Salt,
+                        ..
+                    }
+                )
+            });
+        }
+        assert!(precipitated);
+    }
+
+    #[test]
+    fn test_phase_change_and_ignition() {
+        let mut world = World::new(4, 4);
+        world.set_particle(1, 1, Variant::Ice);
+        let idx = world.get_idx(1, 1);
+        world.particles[idx].temperature = 5.;
+        // walled in so the oil can't flow off before it catches
+        world.set_particle(1, 3, Variant::Wall);
+        world.set_particle(3, 3, Variant::Wall);
+        world.set_particle(2, 3, Variant::Oil);
+        let idx = world.get_idx(2, 3);
+        world.particles[idx].temperature = 300.;
+        world.tick();
+
+        let events: Vec<Event> = world.drain_events().collect();
+        assert!(events.contains(&Event::PhaseChange {
+            x: 1,
+            y: 1,
+            from: Variant::Ice,
+            to: Variant::Water,
+        }));
+        assert!(events.contains(&Event::Ignition {
+            x: 2,
+            y: 3,
+            variant: Variant::Oil,
+        }));
+    }
+
+    #[test]
+    fn test_count_threshold() {
+        let mut world = World::new(4, 4);
+        world.watch_count(Variant::Smoke, 1);
+        world.set_particle(0, 0, Variant::Smoke);
+        world.tick();
+        let crossed = |world: &mut World| {
+            world
+                .drain_events()
+                .filter_map(|e| match e {
+                    Event::CountCrossed { rising, .. } => Some(rising),
+                    _ => None,
+                })
+                .collect::<Vec<bool>>()
+        };
+        assert_eq!(crossed(&mut world), vec![true]);
+
+        // smoke runs out eventually
+        let mut fell = false;
+        for _ in 0..100 {
+            world.tick();
+            fell |= crossed(&mut world) == vec![false];
+        }
+        assert!(fell);
+    }
+}
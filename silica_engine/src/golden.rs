@@ -0,0 +1,186 @@
+// golden image tests: every scenario in golden/ is run and its last frame compared with the
+// png of the same name next to it. after a change that is meant to alter behaviour, run with
+// SILICA_BLESS=1 to rewrite the pngs. a mismatch leaves the actual frame and a diff image,
+// changed cells in red over the dimmed expected frame, in the temp dir
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    render::upscale,
+    scenario::Scenario,
+    snapshot::{self, Snapshot},
+};
+
+const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+const DIFF_SCALE: usize = 8;
+// cells listed in a failure message before it stops counting them out
+const REPORTED: usize = 8;
+
+fn decode(png: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    let mut reader = png::Decoder::new(png)
+        .read_info()
+        .map_err(|e| e.to_string())?;
+    let mut image = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut image).map_err(|e| e.to_string())?;
+    if info.color_type != png::ColorType::Rgba {
+        return Err(format!("expected an rgba image, not {:?}", info.color_type));
+    }
+    Ok((info.width as usize, info.height as usize, image))
+}
+
+fn encode(image: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(image).unwrap();
+    writer.finish().unwrap();
+    png
+}
+
+// "" when they match, otherwise a map of the changed cells and the first few colours
+fn compare(width: usize, expected: &[u8], actual: &[u8]) -> String {
+    let changed: Vec<usize> = (0..expected.len() / 4)
+        .filter(|i| expected[i * 4..i * 4 + 4] != actual[i * 4..i * 4 + 4])
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    let mut report = format!("{} cells differ, marked X\n", changed.len());
+    for row in 0..expected.len() / 4 / width {
+        let line: String = (0..width)
+            .map(|x| match changed.contains(&(x + row * width)) {
+                true => 'X',
+                false => '.',
+            })
+            .collect();
+        report.push_str(&line);
+        report.push('\n');
+    }
+    for i in changed.iter().take(REPORTED) {
+        report.push_str(&format!(
+            "({}, {}): expected {:?}, got {:?}\n",
+            i % width,
+            i / width,
+            &expected[i * 4..i * 4 + 4],
+            &actual[i * 4..i * 4 + 4]
+        ));
+    }
+    report
+}
+
+fn diff_image(expected: &[u8], actual: &[u8]) -> Vec<u8> {
+    expected
+        .chunks_exact(4)
+        .zip(actual.chunks_exact(4))
+        .flat_map(|(e, a)| match e == a {
+            true => [e[0] / 3, e[1] / 3, e[2] / 3, 255],
+            false => [255, 0, 0, 255],
+        })
+        .collect()
+}
+
+// the failure message, or None when the scenario matches its golden
+fn check(scenario_path: &Path, bless: bool) -> Option<String> {
+    let name = scenario_path.file_stem().unwrap().to_string_lossy();
+    let golden_path = scenario_path.with_extension("png");
+    let frame = Scenario::load(scenario_path)
+        .and_then(|scenario| scenario.run())
+        .and_then(|world| {
+            let snapshot = Snapshot {
+                alpha: true,
+                ..Default::default()
+            };
+            snapshot::snapshot_png(&world, &snapshot)
+        });
+    let frame = match frame {
+        Ok(frame) => frame,
+        Err(err) => return Some(format!("{}: {}", name, err)),
+    };
+    if bless {
+        fs::write(&golden_path, &frame).unwrap();
+        return None;
+    }
+
+    let golden = match fs::read(&golden_path) {
+        Ok(golden) => golden,
+        Err(_) => {
+            return Some(format!(
+                "{}: no {}, run with SILICA_BLESS=1 to create it",
+                name,
+                golden_path.display()
+            ))
+        }
+    };
+    let (width, height, expected) = decode(&golden).unwrap();
+    let (actual_width, actual_height, actual) = decode(&frame).unwrap();
+    if (width, height) != (actual_width, actual_height) {
+        return Some(format!(
+            "{}: the golden is {}x{}, the run {}x{}",
+            name, width, height, actual_width, actual_height
+        ));
+    }
+    let report = compare(width, &expected, &actual);
+    if report.is_empty() {
+        return None;
+    }
+
+    let out: PathBuf = env::temp_dir().join("silica_golden");
+    fs::create_dir_all(&out).unwrap();
+    let mut diff = vec![];
+    upscale(
+        &diff_image(&expected, &actual),
+        width,
+        DIFF_SCALE,
+        &mut diff,
+    );
+    let (diff_path, actual_path) = (
+        out.join(format!("{}.diff.png", name)),
+        out.join(format!("{}.actual.png", name)),
+    );
+    let scaled = encode(&diff, width * DIFF_SCALE, height * DIFF_SCALE);
+    fs::write(&diff_path, scaled).unwrap();
+    fs::write(&actual_path, &frame).unwrap();
+    Some(format!(
+        "{}: {}see {} and {}",
+        name,
+        report,
+        diff_path.display(),
+        actual_path.display()
+    ))
+}
+
+#[test]
+fn test_golden_scenarios() {
+    let bless = env::var("SILICA_BLESS").is_ok_and(|v| v == "1");
+    let mut scenarios: Vec<PathBuf> = fs::read_dir(DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "scn"))
+        .collect();
+    scenarios.sort();
+    assert!(!scenarios.is_empty(), "no scenarios in {}", DIR);
+
+    let failures: Vec<String> = scenarios
+        .iter()
+        .filter_map(|path| check(path, bless))
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_compare_reports_changed_cells() {
+    let expected = [[0, 0, 0, 0], [1, 2, 3, 255], [0, 0, 0, 0], [9, 9, 9, 255]].concat();
+    let mut actual = expected.clone();
+    assert_eq!(compare(2, &expected, &actual), "");
+
+    actual[12] = 8;
+    let report = compare(2, &expected, &actual);
+    assert!(report.starts_with("1 cells differ, marked X\n..\n.X\n"));
+    assert!(report.contains("(1, 1): expected [9, 9, 9, 255], got [8, 9, 9, 255]"));
+    assert_eq!(diff_image(&expected, &actual)[12..], [255, 0, 0, 255]);
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+// the eight neighbour offsets, ordered clockwise starting from +x (screen space, y down)
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+// a placeable point that pulls (positive strength) or pushes (negative strength)
+// particles within its radius
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GravityPoint {
+    pub x: i32,
+    pub y: i32,
+    pub strength: f32,
+    pub radius: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gravity {
+    pub direction: (f32, f32),
+    pub points: Vec<GravityPoint>,
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self {
+            direction: (0., 1.),
+            points: Vec::new(),
+        }
+    }
+}
+
+impl Gravity {
+    // point the global pull at the given angle, 0 degrees is straight down
+    pub fn set_angle(&mut self, degrees: f32) {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        self.direction = (-sin, cos);
+    }
+
+    pub fn add_point(&mut self, point: GravityPoint) -> usize {
+        self.points.push(point);
+        self.points.len() - 1
+    }
+
+    pub fn remove_point(&mut self, idx: usize) {
+        if idx < self.points.len() {
+            self.points.remove(idx);
+        }
+    }
+
+    pub fn clear_points(&mut self) {
+        self.points.clear();
+    }
+
+    // summed pull acting on a cell
+    pub fn vector_at(&self, x: i32, y: i32) -> (f32, f32) {
+        let (mut gx, mut gy) = self.direction;
+
+        for point in self.points.iter() {
+            let dx = (point.x - x) as f32;
+            let dy = (point.y - y) as f32;
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist == 0. || dist > point.radius {
+                continue;
+            }
+
+            // inverse square falloff, clamped so cells next to the point don't blow up
+            let pull = point.strength / dist.max(1.).powi(2);
+            gx += dx / dist * pull;
+            gy += dy / dist * pull;
+        }
+
+        (gx, gy)
+    }
+
+    // pull at a cell snapped to one of the eight neighbour offsets, (0, 0) means weightless
+    pub fn direction_at(&self, x: i32, y: i32) -> (i32, i32) {
+        let (gx, gy) = self.vector_at(x, y);
+        if gx.abs() < f32::EPSILON && gy.abs() < f32::EPSILON {
+            return (0, 0);
+        }
+
+        let octant = (gy.atan2(gx) / std::f32::consts::FRAC_PI_4).round() as i32;
+        DIRECTIONS[octant.rem_euclid(8) as usize]
+    }
+}
+
+// rotate a neighbour offset by 45 degree steps, positive is clockwise
+pub fn rotate(dir: (i32, i32), steps: i32) -> (i32, i32) {
+    match DIRECTIONS.iter().position(|d| *d == dir) {
+        Some(i) => DIRECTIONS[(i as i32 + steps).rem_euclid(8) as usize],
+        None => dir,
+    }
+}
+
+// diagonal fall offset, dx = 1 gives (1, 1) when gravity points down
+pub fn spread(dir: (i32, i32), dx: i32) -> (i32, i32) {
+    rotate(dir, -dx)
+}
+
+// perpendicular offset, dx = 1 gives (1, 0) when gravity points down
+pub fn side(dir: (i32, i32), dx: i32) -> (i32, i32) {
+    rotate(dir, -2 * dx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_down() {
+        let gravity = Gravity::default();
+        assert_eq!(gravity.direction_at(10, 10), (0, 1));
+        assert_eq!(spread((0, 1), 1), (1, 1));
+        assert_eq!(side((0, 1), -1), (-1, 0));
+    }
+
+    #[test]
+    fn test_angle_and_points() {
+        let mut gravity = Gravity::default();
+        gravity.set_angle(90.);
+        assert_eq!(gravity.direction_at(0, 0), (-1, 0));
+
+        gravity.direction = (0., 0.);
+        gravity.add_point(GravityPoint {
+            x: 10,
+            y: 10,
+            strength: 50.,
+            radius: 20.,
+        });
+        assert_eq!(gravity.direction_at(5, 10), (1, 0));
+        assert_eq!(gravity.direction_at(15, 15), (-1, -1));
+        assert_eq!(gravity.direction_at(40, 40), (0, 0));
+    }
+}
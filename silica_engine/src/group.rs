@@ -1,49 +1,339 @@
-use std::cell::RefCell;
+use serde::{Deserialize, Serialize};
 
-use crate::variant::*;
+use crate::{
+    elements::Elements,
+    variant::Variant,
+    variant_type::{ParticleColor, VariantProperty, VariantType, VARIANT_COUNT},
+};
 
-#[derive(Clone)]
+// the default catalogue, in picker order
+pub const POWDERS: &str = "Powders";
+pub const LIQUIDS: &str = "Liquids";
+pub const GASES: &str = "Gases";
+pub const CHEMISTRY: &str = "Chemistry";
+pub const LIFE: &str = "Life";
+// walls, other solids and electronics
+pub const SPECIAL: &str = "Special";
+pub const CATEGORIES: [&str; 6] = [POWDERS, LIQUIDS, GASES, CHEMISTRY, LIFE, SPECIAL];
+
+// what a picker shows for an element
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ElementInfo {
+    // short code as in `get_name`, "WATR"
+    pub code: String,
+    pub name: String,
+    pub description: String,
+    // swatch colour, the element's own unless set otherwise
+    pub color: ParticleColor,
+    // for frontends that have icons, a path they understand
+    pub icon: Option<String>,
+    // left out of pickers, search still finds it
+    pub hidden: bool,
+    // only listed when the picker asks for debug elements
+    pub debug: bool,
+    pub variant_type: VariantType,
+}
+
+impl ElementInfo {
+    pub fn new(name: &str, code: &str, variant_type: VariantType) -> ElementInfo {
+        ElementInfo {
+            code: code.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            color: variant_type.color,
+            icon: None,
+            hidden: false,
+            debug: false,
+            variant_type,
+        }
+    }
+
+    pub fn builtin(variant: Variant) -> ElementInfo {
+        let (name, description) = describe(variant);
+        ElementInfo {
+            description: description.to_string(),
+            hidden: variant == Variant::Custom,
+            ..ElementInfo::new(
+                name,
+                variant.get_name(),
+                *VariantType::from_variant(variant),
+            )
+        }
+    }
+
+    // built in or runtime, from whatever the world knows about it
+    pub fn of(elements: &Elements, variant_type: VariantType) -> ElementInfo {
+        match elements.get(variant_type.custom) {
+            Some(element) => ElementInfo::new(&element.name, &element.code, variant_type),
+            None => ElementInfo {
+                color: variant_type.color,
+                variant_type,
+                ..ElementInfo::builtin(variant_type.source_variant)
+            },
+        }
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.name.to_lowercase().contains(&query) || self.code.to_lowercase().starts_with(&query)
+    }
+}
+
+// display name and one line description of the built in elements
+pub fn describe(variant: Variant) -> (&'static str, &'static str) {
+    match variant {
+        Variant::Empty => ("Eraser", "Clears cells back to empty space."),
+        Variant::Wall => ("Wall", "Indestructible, nothing moves through it."),
+        Variant::Sand => ("Sand", "Falls and piles up, melts into glass."),
+        Variant::Water => ("Water", "Flows, boils into steam and dissolves salt."),
+        Variant::Fire => (
+            "Fire",
+            "Heats what it touches, turns to smoke without oxygen.",
+        ),
+        Variant::Smoke => ("Smoke", "What a smothered fire leaves behind."),
+        Variant::Salt => ("Salt", "Dissolves in water."),
+        Variant::SaltWater => (
+            "Salt Water",
+            "Water with salt dissolved in it, conducts electricity.",
+        ),
+        Variant::OXGN => ("Oxygen", "Feeds fire, and burns itself."),
+        Variant::HYGN => ("Hydrogen", "Light and flammable."),
+        Variant::HELM => ("Helium", "Light and inert."),
+        Variant::CARB => ("Carbon", "Inert on its own."),
+        Variant::NITR => ("Nitrogen", "Inert, most of the air."),
+        Variant::IRON => ("Iron", "Conducts electricity, glows when hot."),
+        Variant::CO2 => (
+            "Carbon Dioxide",
+            "Fires breathe it out and plants take it in.",
+        ),
+        Variant::WTVP => ("Steam", "Rises and condenses back into water."),
+        Variant::GOL => ("Life", "Conway's game of life."),
+        Variant::HLIF => ("High Life", "Life that also breeds on six neighbours."),
+        Variant::SEED => ("Seeds", "Every cell dies, two neighbours give birth."),
+        Variant::DNGT => (
+            "Day & Night",
+            "Life rule that is symmetric under inversion.",
+        ),
+        Variant::BTRY => ("Battery", "Powers the wires it touches."),
+        Variant::SWCH => ("Switch", "Lets current through while it is on."),
+        Variant::WIRE => ("Wire", "Carries sparks from batteries."),
+        Variant::Lava => (
+            "Lava",
+            "Molten rock, sets things alight and cools to stone.",
+        ),
+        Variant::Stone => ("Stone", "Heavy powder, melts back into lava."),
+        Variant::Ice => ("Ice", "Melts into water."),
+        Variant::Oil => ("Oil", "Catches fire from flames or heat."),
+        Variant::Acid => ("Acid", "Eats solids and powders, but not glass."),
+        Variant::Plant => ("Plant", "Grows into water and turns CO2 back into oxygen."),
+        Variant::Glass => ("Glass", "Solid, made from molten sand."),
+        Variant::Custom => ("Custom", "Template for runtime elements."),
+    }
+}
+
+// which default group a built in element belongs to, the rest go by property
+pub fn category(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Acid
+        | Variant::OXGN
+        | Variant::HYGN
+        | Variant::HELM
+        | Variant::CARB
+        | Variant::NITR
+        | Variant::CO2 => CHEMISTRY,
+        Variant::GOL | Variant::HLIF | Variant::SEED | Variant::DNGT | Variant::Plant => LIFE,
+        _ => match VariantType::from_variant(variant).variant_property {
+            VariantProperty::Powder => POWDERS,
+            VariantProperty::Liquid => LIQUIDS,
+            VariantProperty::Gas => GASES,
+            VariantProperty::Solid => SPECIAL,
+        },
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VariantGroup {
     pub group_name: String,
-    elements: Vec<Variant>,
+    elements: Vec<ElementInfo>,
 }
 
 impl VariantGroup {
-    pub fn get_elements(&self) -> Vec<Variant> {
-        self.elements.clone()
+    pub fn get_elements(&self) -> Vec<VariantType> {
+        self.elements.iter().map(|e| e.variant_type).collect()
+    }
+
+    pub fn entries(&self) -> &[ElementInfo] {
+        &self.elements
     }
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ElementManager {
-    pub groups: RefCell<Vec<VariantGroup>>,
+    pub groups: Vec<VariantGroup>,
 }
+
 impl ElementManager {
     pub fn new() -> Self {
-        ElementManager {
-            groups: RefCell::new(Vec::new()),
+        ElementManager { groups: Vec::new() }
+    }
+
+    // every built in element sorted into CATEGORIES
+    pub fn builtin() -> Self {
+        let mut manager = ElementManager::new();
+        for group in CATEGORIES {
+            manager.register_group(group, Vec::new());
         }
+        for variant in (0..VARIANT_COUNT as u8).map(Variant::from_u8) {
+            manager.add_to_group(category(variant), ElementInfo::builtin(variant));
+        }
+        manager
     }
 
-    pub fn register_group(&self, group_name: &str, elements: Vec<Variant>) {
-        self.groups.borrow_mut().push(VariantGroup {
-            group_name: group_name.to_string(),
-            elements,
-        });
+    // registering a name again replaces that group's elements
+    pub fn register_group(&mut self, group_name: &str, elements: Vec<ElementInfo>) {
+        match self.group_mut(group_name) {
+            Some(group) => group.elements = elements,
+            None => self.groups.push(VariantGroup {
+                group_name: group_name.to_string(),
+                elements,
+            }),
+        }
+    }
+
+    // adds to an existing group, or starts a new one. an element with the same code is replaced
+    pub fn add_to_group(&mut self, group_name: &str, element: ElementInfo) {
+        match self.group_mut(group_name) {
+            Some(group) => match group.elements.iter_mut().find(|e| e.code == element.code) {
+                Some(existing) => *existing = element,
+                None => group.elements.push(element),
+            },
+            None => self.groups.push(VariantGroup {
+                group_name: group_name.to_string(),
+                elements: vec![element],
+            }),
+        }
     }
 
-    pub fn get_group(&self, group_name: &str) -> Vec<Variant> {
+    // replaces the element with the same code in every group that lists it
+    pub fn refresh(&mut self, element: &ElementInfo) {
+        for existing in self
+            .groups
+            .iter_mut()
+            .flat_map(|group| group.elements.iter_mut())
+        {
+            if existing.code == element.code {
+                *existing = element.clone();
+            }
+        }
+    }
+
+    fn group_mut(&mut self, group_name: &str) -> Option<&mut VariantGroup> {
+        self.groups
+            .iter_mut()
+            .find(|group| group.group_name == group_name)
+    }
+
+    pub fn get_group(&self, group_name: &str) -> Vec<VariantType> {
         self.groups
-            .borrow()
             .iter()
             .find(|group| group.group_name == group_name)
-            .map_or_else(Vec::new, |group| group.elements.clone())
+            .map_or_else(Vec::new, |group| group.get_elements())
+    }
+
+    // what a picker lists for a group
+    pub fn get_entries(&self, group_name: &str, debug: bool) -> Vec<&ElementInfo> {
+        self.groups
+            .iter()
+            .filter(|group| group.group_name == group_name)
+            .flat_map(|group| group.elements.iter())
+            .filter(|e| !e.hidden && (debug || !e.debug))
+            .collect()
     }
 
     pub fn get_group_names(&self) -> Vec<String> {
         self.groups
-            .borrow()
             .iter()
             .map(|group| group.group_name.clone())
             .collect()
     }
+
+    // by exact name or short code, ignoring case
+    pub fn find(&self, name: &str) -> Option<&ElementInfo> {
+        self.iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name) || e.code.eq_ignore_ascii_case(name))
+    }
+
+    // names containing the query or codes starting with it, each element once
+    pub fn search(&self, query: &str) -> Vec<&ElementInfo> {
+        let mut found: Vec<&ElementInfo> = vec![];
+        for element in self.iter().filter(|e| e.matches(query)) {
+            if !found.iter().any(|e| e.code == element.code) {
+                found.push(element);
+            }
+        }
+        found
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ElementInfo> {
+        self.groups.iter().flat_map(|group| group.elements.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant_type::CUSTOM;
+
+    #[test]
+    fn test_builtin_catalogue() {
+        let catalogue = ElementManager::builtin();
+        assert_eq!(catalogue.get_group_names(), CATEGORIES.map(String::from));
+        // every element is listed once
+        assert_eq!(catalogue.iter().count(), VARIANT_COUNT);
+
+        let names = |group| {
+            catalogue
+                .get_entries(group, false)
+                .iter()
+                .map(|e| e.code.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(names(POWDERS).contains(&"SAND".to_string()));
+        assert!(names(LIQUIDS).contains(&"WATR".to_string()));
+        assert!(names(GASES).contains(&"STM".to_string()));
+        assert!(names(CHEMISTRY).contains(&"ACID".to_string()));
+        assert!(names(LIFE).contains(&"GOL".to_string()));
+        // the custom template is hidden
+        assert!(!names(SPECIAL).contains(&"CUST".to_string()));
+        assert!(names(SPECIAL).contains(&"WIRE".to_string()));
+
+        assert_eq!(catalogue.find("swtr").unwrap().name, "Salt Water");
+        let found: Vec<&str> = catalogue
+            .search("wat")
+            .iter()
+            .map(|e| e.code.as_str())
+            .collect();
+        assert_eq!(found, vec!["WATR", "SWTR"]);
+        let found: Vec<&str> = catalogue
+            .search("ST")
+            .iter()
+            .map(|e| e.code.as_str())
+            .collect();
+        assert!(found.contains(&"STNE") && found.contains(&"STM"));
+    }
+
+    #[test]
+    fn test_catalogue_round_trips() {
+        let mut catalogue = ElementManager::builtin();
+        let mut goo = ElementInfo::new("Goo", "GOO", CUSTOM);
+        goo.debug = true;
+        catalogue.add_to_group(LIQUIDS, goo.clone());
+        assert!(!catalogue.get_entries(LIQUIDS, false).contains(&&goo));
+        assert!(catalogue.get_entries(LIQUIDS, true).contains(&&goo));
+
+        let bytes = bincode::serialize(&catalogue).unwrap();
+        let loaded: ElementManager = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(loaded.find("goo"), Some(&goo));
+        assert_eq!(loaded.get_group(LIQUIDS), catalogue.get_group(LIQUIDS));
+    }
 }
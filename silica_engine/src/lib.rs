@@ -1,9 +1,33 @@
 pub mod api;
+pub mod atmosphere;
 pub mod colors;
-
+#[cfg(test)]
+mod conservation;
+#[cfg(feature = "definitions")]
+pub mod definitions;
+pub mod electricity;
+pub mod elements;
+pub mod events;
+#[cfg(test)]
+mod golden;
+pub mod gravity;
 pub mod group;
+pub mod life;
+pub mod lifetime;
+pub mod metadata;
 pub mod particle;
+pub mod plugin;
 pub mod prelude;
+pub mod probe;
+pub mod record;
+pub mod render;
+pub mod rng;
+pub mod scenario;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod snapshot;
+pub mod solution;
+pub mod stats;
 pub mod variant;
 pub mod variant_type;
 pub mod world;
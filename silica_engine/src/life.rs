@@ -0,0 +1,174 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::variant::Variant;
+
+pub const CONWAY: &str = "B3/S23";
+pub const HIGHLIFE: &str = "B36/S23";
+pub const SEEDS: &str = "B2/S";
+pub const DAY_NIGHT: &str = "B3678/S34678";
+
+// birth/survival rule in B/S notation, bit n set means "n live neighbours"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifeRule {
+    pub birth: u16,
+    pub survive: u16,
+}
+
+impl LifeRule {
+    pub fn born(&self, nbrs: u32) -> bool {
+        nbrs <= 8 && self.birth & (1 << nbrs) != 0
+    }
+
+    pub fn survives(&self, nbrs: u32) -> bool {
+        nbrs <= 8 && self.survive & (1 << nbrs) != 0
+    }
+}
+
+impl FromStr for LifeRule {
+    type Err = String;
+
+    // parses "B36/S23" style strings, case insensitive, either half may be empty ("B2/S").
+    // births are only looked for around live cells, so B0 can't be honoured and is rejected
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (b, s_part) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("rule {:?} is missing '/'", s))?;
+
+        let parse_half = |half: &str, prefix: char| -> Result<u16, String> {
+            let mut chars = half.chars();
+            match chars.next() {
+                Some(c) if c.eq_ignore_ascii_case(&prefix) => (),
+                _ => return Err(format!("rule {:?} expected '{}' section", s, prefix)),
+            }
+
+            let mut mask = 0u16;
+            for c in chars {
+                match c.to_digit(10) {
+                    Some(n) if n <= 8 => mask |= 1 << n,
+                    _ => return Err(format!("rule {:?} has invalid neighbour count {:?}", s, c)),
+                }
+            }
+            Ok(mask)
+        };
+
+        let birth = parse_half(b, 'B')?;
+        if birth & 1 != 0 {
+            return Err(format!("rule {:?} can't give birth with no neighbours", s));
+        }
+        Ok(LifeRule {
+            birth,
+            survive: parse_half(s_part, 'S')?,
+        })
+    }
+}
+
+impl Display for LifeRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = |mask: u16| -> String {
+            (0..=8)
+                .filter(|n| mask & (1 << n) != 0)
+                .map(|n| char::from(b'0' + n as u8))
+                .collect()
+        };
+        write!(f, "B{}/S{}", digits(self.birth), digits(self.survive))
+    }
+}
+
+// the rule each life species follows, species only count their own kind as neighbours
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LifeRules {
+    species: Vec<(Variant, LifeRule)>,
+}
+
+impl Default for LifeRules {
+    fn default() -> Self {
+        let rule = |s: &str| s.parse::<LifeRule>().unwrap();
+        Self {
+            species: vec![
+                (Variant::GOL, rule(CONWAY)),
+                (Variant::HLIF, rule(HIGHLIFE)),
+                (Variant::SEED, rule(SEEDS)),
+                (Variant::DNGT, rule(DAY_NIGHT)),
+            ],
+        }
+    }
+}
+
+impl LifeRules {
+    pub fn get(&self, variant: Variant) -> Option<LifeRule> {
+        self.species
+            .iter()
+            .find(|(v, _)| *v == variant)
+            .map(|(_, rule)| *rule)
+    }
+
+    // only variants that already follow a life rule can be reconfigured
+    pub fn set(&mut self, variant: Variant, rule: &str) -> Result<(), String> {
+        let rule = rule.parse::<LifeRule>()?;
+        match self.species.iter_mut().find(|(v, _)| *v == variant) {
+            Some(entry) => {
+                entry.1 = rule;
+                Ok(())
+            }
+            None => Err(format!("{} is not a life species", variant)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.species.is_empty()
+    }
+
+    pub fn nth(&self, i: usize) -> (Variant, LifeRule) {
+        self.species[i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn test_parse_rules() {
+        let highlife: LifeRule = HIGHLIFE.parse().unwrap();
+        assert!(highlife.born(3) && highlife.born(6) && !highlife.born(2));
+        assert!(highlife.survives(2) && highlife.survives(3) && !highlife.survives(6));
+        assert_eq!(highlife.to_string(), HIGHLIFE);
+
+        let seeds: LifeRule = "b2/s".parse().unwrap();
+        assert_eq!(seeds.survive, 0);
+        assert_eq!(seeds.to_string(), SEEDS);
+
+        assert!("B3S23".parse::<LifeRule>().is_err());
+        assert!("B39/S23".parse::<LifeRule>().is_err());
+        assert!("B03/S23".parse::<LifeRule>().is_err());
+    }
+
+    #[test]
+    fn test_set_rule() {
+        let mut rules = LifeRules::default();
+        rules.set(Variant::GOL, HIGHLIFE).unwrap();
+        assert_eq!(rules.get(Variant::GOL), HIGHLIFE.parse().ok());
+        assert!(rules.set(Variant::Sand, CONWAY).is_err());
+    }
+
+    #[test]
+    fn test_block_is_still_life() {
+        let mut world = World::new(8, 8);
+        for (x, y) in [(3, 3), (4, 3), (3, 4), (4, 4)] {
+            world.set_particle(x, y, Variant::GOL);
+        }
+        for _ in 0..10 {
+            world.tick();
+        }
+        assert_eq!(world.get_particle_count(), 4);
+        assert_eq!(world.get_particle(4, 4).get_variant(), Variant::GOL);
+    }
+}
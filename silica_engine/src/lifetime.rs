@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::API,
+    events::Event,
+    metadata::{Value, NO_STATE},
+    particle::Particle,
+    variant::{Variant, EMPTY_CELL},
+    variant_type::VariantType,
+};
+
+// a particle lives between `min` and `max` ticks, then turns into `product`
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Lifetime {
+    pub min: u16,
+    pub max: u16,
+    pub product: Variant,
+}
+
+impl Lifetime {
+    pub const fn new(min: u16, max: u16, product: Variant) -> Lifetime {
+        Lifetime { min, max, product }
+    }
+}
+
+// count the particle's life down by one tick, scheduling it on the first one.
+// returns true if it decayed into its product
+pub fn age(particle: &mut Particle, api: &mut API) -> bool {
+    let lifetime = match particle.variant_type.lifetime {
+        Some(lifetime) => lifetime,
+        None => return false,
+    };
+
+    let mut life = api.world.get_u16(particle, "life");
+    if life == 0 {
+        let span = lifetime.max.saturating_sub(lifetime.min) as i32 + 1;
+        life = lifetime.min.max(1) + api.rand_int(span) as u16;
+    }
+
+    life -= 1;
+    if life > 0 {
+        api.set_field(particle, "life", Value::U16(life)).unwrap();
+        api.set(0, 0, *particle);
+        return false;
+    }
+
+    api.emit(Event::Decay {
+        x: api.x,
+        y: api.y,
+        variant: particle.get_variant(),
+        product: lifetime.product,
+    });
+    if lifetime.product == Variant::Empty {
+        api.set(0, 0, EMPTY_CELL);
+    } else {
+        let variant_type = *VariantType::from_variant(lifetime.product);
+        api.set(
+            0,
+            0,
+            Particle {
+                variant_type,
+                strength: variant_type.strength,
+                // the product starts afresh, its fields at their defaults
+                state: NO_STATE,
+                ..*particle
+            },
+        );
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{variant_type::SMOKE, world::World};
+
+    fn sealed(variant: Variant) -> World {
+        let mut world = World::new(3, 3);
+        for x in 0..3 {
+            for y in 0..3 {
+                world.set_particle(x, y, Variant::Wall);
+            }
+        }
+        world.erase_indestructible(1, 1);
+        world.set_particle(1, 1, variant);
+        world
+    }
+
+    #[test]
+    fn test_smoke_decays_within_lifetime() {
+        let lifetime = SMOKE.lifetime.unwrap();
+        let mut world = sealed(Variant::Smoke);
+        for _ in 0..lifetime.min - 1 {
+            world.tick();
+        }
+        assert_eq!(world.get_particle(1, 1).get_variant(), Variant::Smoke);
+
+        for _ in lifetime.min..=lifetime.max {
+            world.tick();
+        }
+        assert_eq!(world.get_particle(1, 1).get_variant(), Variant::Empty);
+    }
+
+    #[test]
+    fn test_fire_turns_to_smoke() {
+        let mut world = sealed(Variant::Fire);
+        let lifetime = world.get_particle(1, 1).variant_type.lifetime.unwrap();
+        assert_eq!(lifetime.product, Variant::Smoke);
+        for _ in 0..lifetime.max {
+            world.tick();
+            if world.get_particle(1, 1).get_variant() != Variant::Fire {
+                break;
+            }
+        }
+        assert_eq!(world.get_particle(1, 1).get_variant(), Variant::Smoke);
+    }
+
+    #[test]
+    fn test_stable_elements_never_decay() {
+        for variant in [Variant::CARB, Variant::HYGN, Variant::HELM, Variant::NITR] {
+            let mut world = sealed(variant);
+            for _ in 0..500 {
+                world.tick();
+            }
+            assert_eq!(world.get_particle(1, 1).get_variant(), variant);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use std::{borrow::Cow, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    elements::Elements,
+    particle::Particle,
+    solution::BRINE,
+    variant::Variant,
+    variant_type::{VariantType, FLAG_CONDUCTS},
+};
+
+// bytes of element state one particle can hold. it lives in the world's `StateBuffer`,
+// not on the particle, so ra/rb stay free for render variation and movement bookkeeping
+pub const META_BYTES: usize = 16;
+// `Particle::state` of a particle whose fields all hold their defaults
+pub const NO_STATE: u32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldKind {
+    U8,
+    U16,
+    U32,
+    F32,
+}
+
+impl FieldKind {
+    pub fn size(&self) -> usize {
+        match self {
+            FieldKind::U8 => 1,
+            FieldKind::U16 => 2,
+            FieldKind::U32 | FieldKind::F32 => 4,
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<FieldKind> {
+        match n {
+            0 => Some(FieldKind::U8),
+            1 => Some(FieldKind::U16),
+            2 => Some(FieldKind::U32),
+            3 => Some(FieldKind::F32),
+            _ => None,
+        }
+    }
+
+    // "u8", "u16", "u32" or "f32", as definitions files and scripts write them
+    pub fn from_name(name: &str) -> Result<FieldKind, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "u8" => Ok(FieldKind::U8),
+            "u16" => Ok(FieldKind::U16),
+            "u32" => Ok(FieldKind::U32),
+            "f32" => Ok(FieldKind::F32),
+            _ => Err(format!("unknown field kind {:?}", name)),
+        }
+    }
+
+    pub fn zero(&self) -> Value {
+        match self {
+            FieldKind::U8 => Value::U8(0),
+            FieldKind::U16 => Value::U16(0),
+            FieldKind::U32 => Value::U32(0),
+            FieldKind::F32 => Value::F32(0.),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    F32(f32),
+}
+
+impl Value {
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Value::U8(_) => FieldKind::U8,
+            Value::U16(_) => FieldKind::U16,
+            Value::U32(_) => FieldKind::U32,
+            Value::F32(_) => FieldKind::F32,
+        }
+    }
+
+    pub fn to_f32(&self) -> f32 {
+        match *self {
+            Value::U8(v) => v as f32,
+            Value::U16(v) => v as f32,
+            Value::U32(v) => v as f32,
+            Value::F32(v) => v,
+        }
+    }
+
+    // a number of the given kind, integers are clamped into range
+    pub fn from_f32(kind: FieldKind, v: f32) -> Value {
+        match kind {
+            FieldKind::U8 => Value::U8(v.clamp(0., u8::MAX as f32) as u8),
+            FieldKind::U16 => Value::U16(v.clamp(0., u16::MAX as f32) as u16),
+            FieldKind::U32 => Value::U32(v.clamp(0., u32::MAX as f32) as u32),
+            FieldKind::F32 => Value::F32(v),
+        }
+    }
+
+    fn read(kind: FieldKind, bytes: &[u8]) -> Value {
+        match kind {
+            FieldKind::U8 => Value::U8(bytes[0]),
+            FieldKind::U16 => Value::U16(u16::from_le_bytes([bytes[0], bytes[1]])),
+            FieldKind::U32 => Value::U32(u32::from_le_bytes(bytes[..4].try_into().unwrap())),
+            FieldKind::F32 => Value::F32(f32::from_le_bytes(bytes[..4].try_into().unwrap())),
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        match *self {
+            Value::U8(v) => bytes[0] = v,
+            Value::U16(v) => bytes[..2].copy_from_slice(&v.to_le_bytes()),
+            Value::U32(v) => bytes[..4].copy_from_slice(&v.to_le_bytes()),
+            Value::F32(v) => bytes[..4].copy_from_slice(&v.to_le_bytes()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::U8(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{:.2}", v),
+        }
+    }
+}
+
+// a named slot in an element's state, fields are packed in declaration order
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: Cow<'static, str>,
+    pub kind: FieldKind,
+    // what a particle holds until the field is first written
+    pub default: Value,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, kind: FieldKind, default: Value) -> Field {
+        Field {
+            name: Cow::Borrowed(name),
+            kind,
+            default,
+        }
+    }
+
+    // a field declared at runtime, starting at zero
+    pub fn named(name: &str, kind: FieldKind) -> Field {
+        Field {
+            name: Cow::Owned(name.to_string()),
+            kind,
+            default: kind.zero(),
+        }
+    }
+}
+
+static SWITCH_FIELDS: [Field; 1] = [Field::new("on", FieldKind::U8, Value::U8(0))];
+static WIRE_FIELDS: [Field; 1] = [Field::new("state", FieldKind::U8, Value::U8(0))];
+// dissolved salt grains, see `solution`. steam carries what a boiled cell couldn't drop
+static WATER_FIELDS: [Field; 1] = [Field::new("concentration", FieldKind::F32, Value::F32(0.))];
+static BRINE_FIELDS: [Field; 1] = [Field::new(
+    "concentration",
+    FieldKind::F32,
+    Value::F32(BRINE),
+)];
+
+// kept by the engine for every element that conducts, see `electricity`
+pub static SPARK: Field = Field::new("spark", FieldKind::U8, Value::U8(0));
+// and for every element that decays, ticks left until it does, see `lifetime`
+pub static LIFE: Field = Field::new("life", FieldKind::U16, Value::U16(0));
+// room the engine fields take, runtime elements get the rest
+pub const ENGINE_BYTES: usize = 3;
+
+// the fields an element declares itself
+fn own_fields(elements: &Elements, variant: Variant, custom: u16) -> &[Field] {
+    match variant {
+        Variant::SWCH => &SWITCH_FIELDS,
+        Variant::WIRE => &WIRE_FIELDS,
+        Variant::Water | Variant::WTVP => &WATER_FIELDS,
+        Variant::SaltWater => &BRINE_FIELDS,
+        Variant::Custom => elements.get(custom).map_or(&[], |e| &e.fields[..]),
+        _ => &[],
+    }
+}
+
+// which element some state was written for, that decides where each field sits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Layout {
+    variant: Variant,
+    custom: u16,
+    conducts: bool,
+    decays: bool,
+}
+
+impl Layout {
+    fn of(variant_type: &VariantType) -> Layout {
+        Layout {
+            variant: variant_type.source_variant,
+            custom: variant_type.custom,
+            conducts: variant_type.has_flag(FLAG_CONDUCTS),
+            decays: variant_type.lifetime.is_some(),
+        }
+    }
+
+    fn fields(self, elements: &Elements) -> impl Iterator<Item = &Field> {
+        own_fields(elements, self.variant, self.custom)
+            .iter()
+            .chain(self.conducts.then_some(&SPARK))
+            .chain(self.decays.then_some(&LIFE))
+    }
+
+    // byte offset of a field, None past the end of the buffer
+    fn locate<'a>(self, elements: &'a Elements, name: &str) -> Option<(usize, &'a Field)> {
+        let mut offset = 0;
+        for field in self.fields(elements) {
+            if field.name == name {
+                return (offset + field.kind.size() <= META_BYTES).then_some((offset, field));
+            }
+            offset += field.kind.size();
+        }
+        None
+    }
+}
+
+// every field a particle of this type has, its element's own then the engine's
+pub fn schema<'a>(
+    elements: &'a Elements,
+    variant_type: &VariantType,
+) -> impl Iterator<Item = &'a Field> {
+    Layout::of(variant_type).fields(elements)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Slot {
+    layout: Layout,
+    bytes: [u8; META_BYTES],
+}
+
+// the element state of every particle that has any, particles point into it with
+// `Particle::state`. a slot never changes once written, setting a field writes a new
+// one, so copies of a particle can't see each other's changes. fields are matched by
+// name, a particle keeps what it holds through a change of element when the new
+// element has a field of the same name and kind. slots nothing points to any more are
+// reused after the next tick
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StateBuffer {
+    slots: Vec<Slot>,
+    #[serde(skip)]
+    free: Vec<u32>,
+}
+
+impl StateBuffer {
+    fn slot(&self, state: u32) -> Option<&Slot> {
+        self.slots.get((state as usize).checked_sub(1)?)
+    }
+
+    // what the state holds for a field, None when it was written without one
+    fn stored(&self, elements: &Elements, state: u32, field: &Field) -> Option<Value> {
+        let slot = self.slot(state)?;
+        let (offset, stored) = slot.layout.locate(elements, &field.name)?;
+        (stored.kind == field.kind).then(|| Value::read(field.kind, &slot.bytes[offset..]))
+    }
+
+    // None if the particle's element has no such field
+    pub fn get(&self, elements: &Elements, particle: &Particle, name: &str) -> Option<Value> {
+        let field = schema(elements, &particle.variant_type).find(|f| f.name == name)?;
+        Some(
+            self.stored(elements, particle.state, field)
+                .unwrap_or(field.default),
+        )
+    }
+
+    // every field with its value, in layout order
+    pub fn fields(&self, elements: &Elements, particle: &Particle) -> Vec<(String, Value)> {
+        schema(elements, &particle.variant_type)
+            .map(|field| {
+                let value = self
+                    .stored(elements, particle.state, field)
+                    .unwrap_or(field.default);
+                (field.name.to_string(), value)
+            })
+            .collect()
+    }
+
+    pub fn set(
+        &mut self,
+        elements: &Elements,
+        particle: &mut Particle,
+        name: &str,
+        value: Value,
+    ) -> Result<(), String> {
+        let layout = Layout::of(&particle.variant_type);
+        let (_, field) = layout
+            .locate(elements, name)
+            .ok_or_else(|| format!("{} has no field {:?}", elements.code_of(particle), name))?;
+        if field.kind != value.kind() {
+            return Err(format!(
+                "field {:?} of {} is {:?}, got {:?}",
+                name,
+                elements.code_of(particle),
+                field.kind,
+                value
+            ));
+        }
+
+        let mut bytes = [0; META_BYTES];
+        let mut offset = 0;
+        for field in layout.fields(elements) {
+            let size = field.kind.size();
+            if offset + size > META_BYTES {
+                break;
+            }
+            let value = match field.name == name {
+                true => value,
+                false => self
+                    .stored(elements, particle.state, field)
+                    .unwrap_or(field.default),
+            };
+            value.write(&mut bytes[offset..]);
+            offset += size;
+        }
+        self.write(particle, Slot { layout, bytes });
+        Ok(())
+    }
+
+    // the particle's state as bytes in its element's layout, for plugins
+    pub fn pack(&self, elements: &Elements, particle: &Particle) -> [u8; META_BYTES] {
+        let mut bytes = [0; META_BYTES];
+        let mut offset = 0;
+        for (_, value) in self.fields(elements, particle) {
+            if offset + value.kind().size() > META_BYTES {
+                break;
+            }
+            value.write(&mut bytes[offset..]);
+            offset += value.kind().size();
+        }
+        bytes
+    }
+
+    pub fn unpack(
+        &mut self,
+        elements: &Elements,
+        particle: &mut Particle,
+        bytes: [u8; META_BYTES],
+    ) {
+        let layout = Layout::of(&particle.variant_type);
+        match layout.fields(elements).next() {
+            Some(_) => self.write(particle, Slot { layout, bytes }),
+            None => particle.state = NO_STATE,
+        }
+    }
+
+    fn write(&mut self, particle: &mut Particle, slot: Slot) {
+        if self.slot(particle.state) == Some(&slot) {
+            return;
+        }
+        particle.state = match self.free.pop() {
+            Some(state) => {
+                self.slots[state as usize - 1] = slot;
+                state
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() as u32
+            }
+        };
+    }
+
+    // slots in use
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // free every slot no particle in `live` points to
+    pub(crate) fn sweep<'a>(&mut self, live: impl Iterator<Item = &'a Particle>) {
+        let mut used = vec![false; self.slots.len()];
+        for particle in live {
+            if let Some(i) = (particle.state as usize).checked_sub(1) {
+                if i < used.len() {
+                    used[i] = true;
+                }
+            }
+        }
+        // freed from the back first, so low slots are reused before high ones
+        self.free = (1..=self.slots.len() as u32)
+            .rev()
+            .filter(|state| !used[*state as usize - 1])
+            .collect();
+    }
+}
+
+// a runtime element's own fields, they share the buffer with the engine's
+pub fn check_fields(fields: &[Field]) -> Result<(), String> {
+    for (i, field) in fields.iter().enumerate() {
+        if field.name.is_empty() {
+            return Err("field without a name".to_string());
+        }
+        if field.name == SPARK.name || field.name == LIFE.name {
+            return Err(format!("field {:?} is kept by the engine", field.name));
+        }
+        if fields[..i].iter().any(|f| f.name == field.name) {
+            return Err(format!("field {:?} is declared twice", field.name));
+        }
+        if field.default.kind() != field.kind {
+            return Err(format!(
+                "default of field {:?} isn't a {:?}",
+                field.name, field.kind
+            ));
+        }
+    }
+    let size: usize = fields.iter().map(|f| f.kind.size()).sum();
+    if size > META_BYTES - ENGINE_BYTES {
+        return Err(format!(
+            "fields take {} bytes, elements have room for {}",
+            size,
+            META_BYTES - ENGINE_BYTES
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        variant_type::{CUSTOM, SAND, SWCH, WATER, WIRE, WTVP},
+        world::World,
+    };
+
+    #[test]
+    fn test_schemas_fit() {
+        let elements = Elements::default();
+        for n in 0..crate::variant_type::VARIANT_COUNT {
+            let variant_type = VariantType::from_variant(Variant::from_u8(n as u8));
+            let size: usize = schema(&elements, variant_type).map(|f| f.kind.size()).sum();
+            assert!(size <= META_BYTES);
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut world = World::new(2, 2);
+        let mut switch = Particle::new(SWCH, 0, 0);
+        assert_eq!(world.get_field(&switch, "on"), Some(Value::U8(0)));
+        let before = switch;
+        world.set_field(&mut switch, "on", Value::U8(1)).unwrap();
+        assert_eq!(world.get_u8(&switch, "on"), 1);
+        // copies keep what they had
+        assert_eq!(world.get_u8(&before, "on"), 0);
+        assert!(world.set_field(&mut switch, "on", Value::F32(1.)).is_err());
+        assert!(world
+            .set_field(&mut switch, "charge", Value::U8(1))
+            .is_err());
+
+        // render variation doesn't touch element state
+        switch.ra = 7;
+        switch.rb = 9;
+        assert_eq!(world.get_u8(&switch, "on"), 1);
+
+        let sand = Particle::new(SAND, 0, 0);
+        assert_eq!(world.get_field(&sand, "on"), None);
+        assert_eq!(world.get_u8(&Particle::new(WIRE, 0, 0), "state"), 0);
+        // switches conduct, so the engine keeps a spark for them too
+        assert_eq!(world.get_u8(&switch, "spark"), 0);
+    }
+
+    #[test]
+    fn test_state_follows_the_particle() {
+        let mut world = World::new(1, 1);
+        let mut water = Particle::new(WATER, 0, 0);
+        world
+            .set_field(&mut water, "concentration", Value::F32(0.75))
+            .unwrap();
+
+        // boiling keeps the salt, the steam has a field of the same name
+        let steam = Particle {
+            variant_type: WTVP,
+            ..water
+        };
+        assert_eq!(world.get_f32(&steam, "concentration"), 0.75);
+
+        // slots nothing points to are reused after a tick
+        world.particles[0] = water;
+        let mut spare = Particle::new(WATER, 0, 0);
+        world
+            .set_field(&mut spare, "concentration", Value::F32(0.5))
+            .unwrap();
+        assert_eq!(world.state.len(), 2);
+        world.tick();
+        assert_eq!(world.state.len(), 1);
+        let mut reused = Particle::new(WATER, 0, 0);
+        world
+            .set_field(&mut reused, "concentration", Value::F32(0.25))
+            .unwrap();
+        assert_eq!(reused.state, spare.state);
+        assert_eq!(world.get_f32(&world.particles[0], "concentration"), 0.75);
+    }
+
+    #[test]
+    fn test_runtime_fields() {
+        let mut world = World::new(2, 2);
+        let goo = world.register_element("Goo", "GOO", CUSTOM, None);
+        let fields = vec![
+            Field::named("charge", FieldKind::U8),
+            Field::new("growth", FieldKind::F32, Value::F32(1.)),
+        ];
+        world.register_fields(goo, fields).unwrap();
+
+        let mut particle = Particle::new(goo, 0, 0);
+        assert_eq!(world.get_f32(&particle, "growth"), 1.);
+        world
+            .set_field(&mut particle, "charge", Value::U8(3))
+            .unwrap();
+        assert_eq!(
+            world.fields(&particle),
+            vec![
+                ("charge".to_string(), Value::U8(3)),
+                ("growth".to_string(), Value::F32(1.))
+            ]
+        );
+
+        assert!(world
+            .register_fields(goo, vec![Field::named("life", FieldKind::U8)])
+            .is_err());
+        let big = (0..4).map(|i| Field::named(&format!("f{}", i), FieldKind::U32));
+        assert!(world.register_fields(goo, big.collect()).is_err());
+        assert!(world.register_fields(SAND, vec![]).is_err());
+    }
+}
@@ -1,6 +1,15 @@
-use crate::{api::API, prelude::ParticleColor, variant::Variant, variant_type};
+use crate::{
+    api::API,
+    colors::{SPARK_COLOR, WIRE_HEAD_COLOR, WIRE_TAIL_COLOR},
+    electricity,
+    metadata::NO_STATE,
+    prelude::ParticleColor,
+    rng, solution,
+    variant::{Variant, WireState},
+    variant_type,
+    world::World,
+};
 
-use rand::Rng;
 use serde::{Deserialize, Serialize};
 use variant_type::{get_variant, VariantType};
 
@@ -15,6 +24,8 @@ pub struct Particle {
     pub modified: bool,
     pub velocity: Velocity,
     pub temperature: f32,
+    // handle into the world's element state, read it with `World::get_field`
+    pub state: u32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -34,6 +45,7 @@ impl Default for Particle {
             modified: false,
             velocity: Velocity { x: 0., y: 0. },
             temperature: 0.,
+            state: NO_STATE,
         }
     }
 }
@@ -41,20 +53,24 @@ impl Particle {
     pub fn new(variant_type: VariantType, _ra: u8, rb: u8) -> Particle {
         Particle {
             variant_type: variant_type,
-            ra: 100 + rand::thread_rng().gen_range(0..=1) * 50,
+            ra: 100 + rng::rand_int(2) as u8 * 50,
             rb,
             clock: 0,
             strength: 0,
             modified: false,
             velocity: Velocity { x: 0., y: 0. },
-            temperature: 0.,
+            // new particles start at their element's base temperature rather than 0, so
+            // lava spawns hot, ice cold and everything else at room temperature
+            temperature: variant_type.base_temperature,
+            // fields start at their defaults, brine at its usual concentration
+            state: NO_STATE,
         }
     }
 
     // save method that only takes the color of the particle
 
-    pub fn save(&self) -> (u8, u8, u8) {
-        let color = particle_to_color(*self);
+    pub fn save(&self, world: &World) -> (u8, u8, u8) {
+        let color = particle_to_color(world, *self);
         (color.r, color.g, color.b)
     }
 
@@ -82,61 +98,119 @@ pub fn color_to_particle(color: (u8, u8, u8)) -> Particle {
     particle
 }
 
-pub fn particle_to_color(particle: Particle) -> ParticleColor {
+// the base colour comes from the particle's type, so overridden and runtime elements show theirs
+pub fn particle_to_color(world: &World, particle: Particle) -> ParticleColor {
     let res = match particle.get_variant() {
-        Variant::Empty => variant_type::EMPTY.color,
-        Variant::Wall => variant_type::WALL.color,
+        Variant::Empty => particle.variant_type.color,
+        Variant::Wall => particle.variant_type.color,
         Variant::Sand => {
             // vary color based on ra
-            let mut color = variant_type::SAND.color;
-            color.whiten(particle.temperature);
+            let mut color = particle.variant_type.color;
+            color.incandesce(particle.temperature);
 
             color.vary_color(particle.ra as i32)
         }
         Variant::Water => {
             // vary color based on ra
-            let mut color = variant_type::WATER.color;
-            color.whiten(particle.temperature);
+            let mut color = particle.variant_type.color;
+            color.incandesce(particle.temperature);
 
             color.vary_color(particle.ra as i32)
         }
         Variant::Fire => {
             // vary color based on ra
-            let mut color = variant_type::FIRE.color;
+            let mut color = particle.variant_type.color;
             //  color.fire_color();
 
             color.vary_color(particle.ra as i32)
         }
         Variant::Smoke => {
             // vary color based on ra
-            let mut color = variant_type::SMOKE.color;
+            let mut color = particle.variant_type.color;
             color.darken_by_strength(particle.strength);
             color
         }
-        Variant::Salt => variant_type::SALT.color,
-        Variant::SaltWater => variant_type::SALT_WATER.color,
-        Variant::OXGN => variant_type::OXGN.color,
-        Variant::HYGN => variant_type::HYGN.color,
-        Variant::HELM => variant_type::HELM.color,
-        Variant::CARB => variant_type::CARB.color,
-        Variant::NITR => variant_type::NITR.color,
+        Variant::Salt => particle.variant_type.color,
+        Variant::SaltWater => {
+            // tint from fresh to salty water by how much is dissolved, fresh as water is now
+            let fresh = world.elements.builtin(Variant::Water).color.to_rgb8();
+            let salty = particle.variant_type.color.to_rgb8();
+            let concentration = world.get_f32(&particle, "concentration");
+            let amount = (concentration / solution::SATURATION * 255.).min(255.) as u8;
+            let (r, g, b) = interpolate(&salty, &fresh, amount, 255);
+            ParticleColor::from_rgba((r, g, b, 255))
+        }
+        Variant::OXGN => particle.variant_type.color,
+        Variant::HYGN => particle.variant_type.color,
+        Variant::HELM => particle.variant_type.color,
+        Variant::CARB => particle.variant_type.color,
+        Variant::NITR => particle.variant_type.color,
+        _ if electricity::spark_of(world, &particle) != 0 => {
+            // live sparks flash, refractory conductors glow as they cool
+            let mut color = SPARK_COLOR;
+            let spark = electricity::spark_of(world, &particle);
+            color.darken_by_strength((electricity::SPARK_LIFE - spark) * 40);
+            color
+        }
         Variant::IRON => {
             // vary color based on ra
-            let mut color = variant_type::IRON.color;
-            color.whiten(particle.temperature);
+            let mut color = particle.variant_type.color;
+            color.incandesce(particle.temperature);
 
             color.vary_color(particle.ra as i32)
         }
-        Variant::CO2 => variant_type::CO2.color,
-        Variant::WTVP => variant_type::WTVP.color,
-        Variant::GOL => variant_type::GOL.color,
+        Variant::CO2 => particle.variant_type.color,
+        Variant::WTVP => particle.variant_type.color,
+        Variant::GOL => particle.variant_type.color,
+        Variant::HLIF => particle.variant_type.color,
+        Variant::SEED => particle.variant_type.color,
+        Variant::DNGT => particle.variant_type.color,
+        Variant::BTRY => particle.variant_type.color,
+        Variant::WIRE => match WireState::from_u8(world.get_u8(&particle, "state")) {
+            WireState::Conductor => particle.variant_type.color,
+            WireState::Head => WIRE_HEAD_COLOR,
+            WireState::Tail => WIRE_TAIL_COLOR,
+        },
+        Variant::SWCH => {
+            let mut color = particle.variant_type.color;
+            if !electricity::switch_on(world, &particle) {
+                color.darken_by_strength(128);
+            }
+            color
+        }
         Variant::Glass => {
             // vary color based on ra
-            let mut color = variant_type::GLASS.color;
-            color.whiten(particle.temperature);
+            let mut color = particle.variant_type.color;
+            color.incandesce(particle.temperature);
 
             color.vary_color(particle.ra as i32)
         }
+        Variant::Lava => {
+            let mut color = particle.variant_type.color;
+            color.vary_color(particle.ra as i32)
+        }
+        Variant::Stone => {
+            let mut color = particle.variant_type.color;
+            color.incandesce(particle.temperature);
+
+            color.vary_color(particle.ra as i32)
+        }
+        Variant::Ice => particle.variant_type.color,
+        Variant::Oil => particle.variant_type.color,
+        Variant::Acid => {
+            // fades as it gets used up
+            let mut color = particle.variant_type.color;
+            color.darken_by_strength(particle.strength * 16);
+            color
+        }
+        Variant::Plant => {
+            let mut color = particle.variant_type.color;
+            color.vary_color(particle.ra as i32)
+        }
+        Variant::Custom => {
+            let mut color = particle.variant_type.color;
+            color.vary_color(particle.ra as i32)
+        }
     };
 
     res
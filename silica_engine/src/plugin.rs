@@ -0,0 +1,601 @@
+use std::{
+    any::Any,
+    ffi::{c_char, c_void, CStr},
+    sync::Arc,
+};
+
+use crate::{
+    api::API,
+    elements::Behaviour,
+    metadata::{Field, FieldKind, META_BYTES},
+    particle::Particle,
+    variant::Variant,
+    variant_type::{ParticleColor, VariantProperty, VariantType, CUSTOM, EMPTY, WALL},
+    world::World,
+};
+
+// bumped on any change to the types below, plugins built against another version are refused
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+pub const ABI_VERSION_SYMBOL: &[u8] = b"silica_plugin_abi_version";
+pub const REGISTER_SYMBOL: &[u8] = b"silica_plugin_register";
+
+pub type AbiVersionFn = extern "C" fn() -> u32;
+pub type RegisterFn = extern "C" fn(registrar: *mut Registrar);
+// return true if the particle changed anything, like the update functions in `variant`
+pub type UpdateFn = extern "C" fn(host: *mut HostApi, me: CParticle) -> bool;
+
+// a built in element (`custom` 0) or a runtime one
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CElement {
+    pub variant: u8,
+    pub custom: u16,
+}
+
+impl CElement {
+    pub fn of(variant_type: &VariantType) -> CElement {
+        CElement {
+            variant: variant_type.source_variant as u8,
+            custom: variant_type.custom,
+        }
+    }
+
+    fn variant_type(&self, world: &World) -> Option<VariantType> {
+        match Variant::from_u8(self.variant) {
+            Variant::Custom => world.elements.get(self.custom).map(|e| e.variant_type),
+            variant => Some(*VariantType::from_variant(variant)),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CParticle {
+    pub element: CElement,
+    pub ra: u8,
+    pub rb: u8,
+    pub strength: u8,
+    pub temperature: f32,
+    // the element's fields packed in declaration order, little endian. elements that
+    // conduct have a u8 "spark" after their own, elements that decay a u16 "life" after that
+    pub meta: [u8; META_BYTES],
+}
+
+impl CParticle {
+    // a new particle, its fields all zero. start from `get` to keep a cell's state
+    pub fn of(particle: &Particle) -> CParticle {
+        CParticle {
+            element: CElement::of(&particle.variant_type),
+            ra: particle.ra,
+            rb: particle.rb,
+            strength: particle.strength,
+            temperature: particle.temperature,
+            meta: [0; META_BYTES],
+        }
+    }
+
+    fn in_world(world: &World, particle: &Particle) -> CParticle {
+        CParticle {
+            meta: world.state.pack(&world.elements, particle),
+            ..CParticle::of(particle)
+        }
+    }
+
+    // unknown elements come back as empty cells
+    fn to_particle(self, world: &mut World) -> Particle {
+        let variant_type = self.element.variant_type(world).unwrap_or(EMPTY);
+        let mut particle = Particle {
+            ra: self.ra,
+            rb: self.rb,
+            strength: self.strength,
+            temperature: self.temperature,
+            ..Particle::new(variant_type, 0, 0)
+        };
+        world
+            .state
+            .unpack(&world.elements, &mut particle, self.meta);
+        particle
+    }
+}
+
+// the engine side of an update call, `ctx` is only valid for the duration of the call
+#[repr(C)]
+pub struct HostApi {
+    pub ctx: *mut c_void,
+    pub get: extern "C" fn(ctx: *mut c_void, dx: i32, dy: i32) -> CParticle,
+    pub set: extern "C" fn(ctx: *mut c_void, dx: i32, dy: i32, particle: CParticle),
+    pub rand_int: extern "C" fn(ctx: *mut c_void, n: i32) -> i32,
+    pub get_temperature: extern "C" fn(ctx: *mut c_void, dx: i32, dy: i32) -> f32,
+    pub set_temperature: extern "C" fn(ctx: *mut c_void, dx: i32, dy: i32, temperature: f32),
+}
+
+#[repr(C)]
+pub struct ElementDef {
+    pub name: *const c_char,
+    pub code: *const c_char,
+    pub color: [u8; 4],
+    pub weight: u8,
+    pub strength: u8,
+    // VariantProperty::to_u8
+    pub property: u8,
+    pub flags: u8,
+    pub base_temperature: f32,
+    pub update: Option<UpdateFn>,
+}
+
+// `element` touching `with` turns into `becomes` (and `with` into `other_becomes`), once in `chance` ticks
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReactionDef {
+    pub element: CElement,
+    pub with: CElement,
+    pub chance: u32,
+    pub becomes: CElement,
+    pub other_becomes: CElement,
+}
+
+// handed to the plugin's register function
+#[repr(C)]
+pub struct Registrar {
+    pub ctx: *mut c_void,
+    // returns custom 0 if the definition was rejected
+    pub register_element: extern "C" fn(ctx: *mut c_void, def: *const ElementDef) -> CElement,
+    // only for elements registered by the same plugin
+    pub register_reaction: extern "C" fn(ctx: *mut c_void, def: *const ReactionDef) -> bool,
+    // adds a field to one of the plugin's elements, `kind` is FieldKind as u8
+    pub register_field:
+        extern "C" fn(ctx: *mut c_void, element: CElement, name: *const c_char, kind: u8) -> bool,
+}
+
+impl Registrar {
+    pub fn element(&mut self, def: &ElementDef) -> CElement {
+        (self.register_element)(self.ctx, def)
+    }
+
+    pub fn reaction(&mut self, def: &ReactionDef) -> bool {
+        (self.register_reaction)(self.ctx, def)
+    }
+
+    pub fn field(&mut self, element: CElement, name: &CStr, kind: FieldKind) -> bool {
+        (self.register_field)(self.ctx, element, name.as_ptr(), kind as u8)
+    }
+}
+
+// exports the two symbols a plugin library needs, given a `fn(&mut Registrar)`
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub extern "C" fn silica_plugin_abi_version() -> u32 {
+            $crate::plugin::PLUGIN_ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn silica_plugin_register(registrar: *mut $crate::plugin::Registrar) {
+            $register(unsafe { &mut *registrar })
+        }
+    };
+}
+
+fn in_reach(dx: i32, dy: i32) -> bool {
+    (-2..=2).contains(&dx) && (-2..=2).contains(&dy)
+}
+
+// the callbacks must never panic, unwinding into plugin code would abort
+extern "C" fn host_get(ctx: *mut c_void, dx: i32, dy: i32) -> CParticle {
+    let api = unsafe { &mut *(ctx as *mut API) };
+    if !in_reach(dx, dy) {
+        return CParticle::of(&Particle::new(WALL, 0, 0));
+    }
+    let particle = api.get(dx, dy);
+    CParticle::in_world(api.world, &particle)
+}
+
+extern "C" fn host_set(ctx: *mut c_void, dx: i32, dy: i32, particle: CParticle) {
+    let api = unsafe { &mut *(ctx as *mut API) };
+    if in_reach(dx, dy) {
+        let particle = particle.to_particle(api.world);
+        api.set(dx, dy, particle);
+    }
+}
+
+extern "C" fn host_rand_int(ctx: *mut c_void, n: i32) -> i32 {
+    let api = unsafe { &mut *(ctx as *mut API) };
+    if n <= 0 {
+        return 0;
+    }
+    api.rand_int(n)
+}
+
+// the air is only within reach like the cells are, out of reach reads as 0 and writes are ignored
+extern "C" fn host_get_temperature(ctx: *mut c_void, dx: i32, dy: i32) -> f32 {
+    let api = unsafe { &mut *(ctx as *mut API) };
+    if !in_reach(dx, dy) || !api.inside(dx, dy) {
+        return 0.;
+    }
+    api.world.get_temperature(api.x + dx, api.y + dy)
+}
+
+extern "C" fn host_set_temperature(ctx: *mut c_void, dx: i32, dy: i32, temperature: f32) {
+    let api = unsafe { &mut *(ctx as *mut API) };
+    if in_reach(dx, dy) {
+        api.world
+            .set_temperature(api.x + dx, api.y + dy, temperature);
+    }
+}
+
+struct PluginElement {
+    update: Option<UpdateFn>,
+    reactions: Vec<ReactionDef>,
+    // keeps the library mapped while its functions can still be called
+    _library: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Behaviour for PluginElement {
+    fn update(&self, particle: Particle, api: &mut API) -> bool {
+        if !self.reactions.is_empty() {
+            let (dx, dy) = api.rand_vec();
+            let nbr = CElement::of(&api.get(dx, dy).variant_type);
+            for reaction in self.reactions.iter().filter(|r| r.with == nbr) {
+                if !api.once_in(reaction.chance.max(1) as i32) {
+                    continue;
+                }
+                let becomes = reaction.becomes.variant_type(api.world).unwrap_or(EMPTY);
+                let other = reaction
+                    .other_becomes
+                    .variant_type(api.world)
+                    .unwrap_or(EMPTY);
+                api.set(0, 0, Particle::new(becomes, 0, 0));
+                api.set(dx, dy, Particle::new(other, 0, 0));
+                return true;
+            }
+        }
+
+        match self.update {
+            Some(update) => {
+                let mut host = HostApi {
+                    ctx: api as *mut API as *mut c_void,
+                    get: host_get,
+                    set: host_set,
+                    rand_int: host_rand_int,
+                    get_temperature: host_get_temperature,
+                    set_temperature: host_set_temperature,
+                };
+                let me = CParticle::in_world(api.world, &particle);
+                update(&mut host, me)
+            }
+            None => false,
+        }
+    }
+}
+
+struct Registration<'a> {
+    world: &'a mut World,
+    elements: Vec<(String, String, VariantType, Option<UpdateFn>)>,
+    reactions: Vec<ReactionDef>,
+    errors: Vec<String>,
+}
+
+fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let s = unsafe { CStr::from_ptr(ptr) };
+    s.to_str().ok().map(str::to_string)
+}
+
+extern "C" fn register_element(ctx: *mut c_void, def: *const ElementDef) -> CElement {
+    let registration = unsafe { &mut *(ctx as *mut Registration) };
+    let rejected = CElement {
+        variant: Variant::Custom as u8,
+        custom: 0,
+    };
+    let Some(def) = (unsafe { def.as_ref() }) else {
+        return rejected;
+    };
+    let Some(name) = c_string(def.name) else {
+        registration
+            .errors
+            .push("element without a name".to_string());
+        return rejected;
+    };
+    if def.property > VariantProperty::Gas.to_u8() {
+        registration
+            .errors
+            .push(format!("{}: unknown property {}", name, def.property));
+        return rejected;
+    }
+
+    let code = c_string(def.code).unwrap_or_else(|| name.to_uppercase());
+    let variant_type = VariantType {
+        color: ParticleColor::from_rgba((def.color[0], def.color[1], def.color[2], def.color[3])),
+        weight: def.weight,
+        strength: def.strength,
+        variant_property: VariantProperty::from_u8(def.property),
+        flags: def.flags,
+        base_temperature: def.base_temperature,
+        ..CUSTOM
+    };
+    let variant_type = registration
+        .world
+        .register_element(&name, &code, variant_type, None);
+    registration
+        .elements
+        .push((name, code, variant_type, def.update));
+    CElement::of(&variant_type)
+}
+
+extern "C" fn register_reaction(ctx: *mut c_void, def: *const ReactionDef) -> bool {
+    let registration = unsafe { &mut *(ctx as *mut Registration) };
+    let Some(def) = (unsafe { def.as_ref() }) else {
+        return false;
+    };
+    let own = registration
+        .elements
+        .iter()
+        .any(|(_, _, variant_type, _)| CElement::of(variant_type) == def.element);
+    if !own {
+        registration.errors.push(format!(
+            "reaction for an element the plugin didn't register: {:?}",
+            def.element
+        ));
+        return false;
+    }
+    registration.reactions.push(*def);
+    true
+}
+
+extern "C" fn register_field(
+    ctx: *mut c_void,
+    element: CElement,
+    name: *const c_char,
+    kind: u8,
+) -> bool {
+    let registration = unsafe { &mut *(ctx as *mut Registration) };
+    let own = registration
+        .elements
+        .iter()
+        .find(|(_, _, variant_type, _)| CElement::of(variant_type) == element)
+        .map(|(_, _, variant_type, _)| *variant_type);
+    let (Some(variant_type), Some(name), Some(kind)) =
+        (own, c_string(name), FieldKind::from_u8(kind))
+    else {
+        registration.errors.push(format!(
+            "field for an element the plugin didn't register, or of unknown kind: {:?}",
+            element
+        ));
+        return false;
+    };
+
+    let mut fields = registration
+        .world
+        .elements
+        .get(variant_type.custom)
+        .map_or_else(Vec::new, |e| e.fields.clone());
+    fields.push(Field::named(&name, kind));
+    match registration.world.register_fields(variant_type, fields) {
+        Ok(()) => true,
+        Err(e) => {
+            registration.errors.push(e);
+            false
+        }
+    }
+}
+
+fn check_version(abi_version: u32) -> Result<(), String> {
+    if abi_version != PLUGIN_ABI_VERSION {
+        return Err(format!(
+            "plugin was built for ABI version {}, this engine uses {}",
+            abi_version, PLUGIN_ABI_VERSION
+        ));
+    }
+    Ok(())
+}
+
+// run a plugin's register function against the world. `library` is whatever
+// has to stay alive for the plugin's functions to remain callable.
+// elements registered before a rejected definition stay in the world, without behaviour
+pub fn install(
+    world: &mut World,
+    abi_version: u32,
+    register: RegisterFn,
+    library: Option<Arc<dyn Any + Send + Sync>>,
+) -> Result<Vec<VariantType>, String> {
+    check_version(abi_version)?;
+
+    let mut registration = Registration {
+        world,
+        elements: vec![],
+        reactions: vec![],
+        errors: vec![],
+    };
+    let mut registrar = Registrar {
+        ctx: &mut registration as *mut Registration as *mut c_void,
+        register_element,
+        register_reaction,
+        register_field,
+    };
+    register(&mut registrar);
+
+    if !registration.errors.is_empty() {
+        return Err(registration.errors.join("\n"));
+    }
+
+    // now that all reactions are known, give every element its behaviour
+    let mut elements = vec![];
+    for (name, code, variant_type, update) in registration.elements {
+        let reactions: Vec<ReactionDef> = registration
+            .reactions
+            .iter()
+            .filter(|r| r.element == CElement::of(&variant_type))
+            .copied()
+            .collect();
+        let behaviour: Option<Arc<dyn Behaviour>> = if update.is_some() || !reactions.is_empty() {
+            Some(Arc::new(PluginElement {
+                update,
+                reactions,
+                _library: library.clone(),
+            }))
+        } else {
+            None
+        };
+        elements.push(
+            registration
+                .world
+                .register_element(&name, &code, variant_type, behaviour),
+        );
+    }
+    Ok(elements)
+}
+
+// load a compiled element pack, see `declare_plugin!`
+#[cfg(feature = "plugins")]
+pub fn load_plugin(
+    world: &mut World,
+    path: impl AsRef<std::ffi::OsStr>,
+) -> Result<Vec<VariantType>, String> {
+    let library = unsafe { libloading::Library::new(path) }.map_err(|e| e.to_string())?;
+    let not_a_plugin = |e: libloading::Error| format!("not a silica plugin: {}", e);
+    let abi_version = unsafe {
+        library
+            .get::<AbiVersionFn>(ABI_VERSION_SYMBOL)
+            .map_err(not_a_plugin)?()
+    };
+    // check before calling anything else the library exports
+    check_version(abi_version)?;
+    let register = unsafe {
+        *library
+            .get::<RegisterFn>(REGISTER_SYMBOL)
+            .map_err(not_a_plugin)?
+    };
+    install(world, abi_version, register, Some(Arc::new(library)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what a plugin crate would write, minus the #[no_mangle] exports
+    extern "C" fn rust_update(host: *mut HostApi, mut me: CParticle) -> bool {
+        let host = unsafe { &mut *host };
+        let below = (host.get)(host.ctx, 0, 1);
+        if below.element.variant == Variant::Empty as u8 {
+            // its "fallen" field, the first in its layout
+            me.meta[0] += 1;
+            (host.set)(host.ctx, 0, 1, me);
+            (host.set)(host.ctx, 0, 0, CParticle::of(&Particle::new(EMPTY, 0, 0)));
+            return true;
+        }
+        false
+    }
+
+    extern "C" fn register(registrar: *mut Registrar) {
+        let registrar = unsafe { &mut *registrar };
+        let rust = registrar.element(&ElementDef {
+            name: c"Rust".as_ptr(),
+            code: c"RUST".as_ptr(),
+            color: [140, 70, 20, 255],
+            weight: 90,
+            strength: 0,
+            property: VariantProperty::Solid.to_u8(),
+            flags: 0,
+            base_temperature: 22.,
+            update: Some(rust_update),
+        });
+        registrar.field(rust, c"fallen", FieldKind::U8);
+        // rust eats iron, leaving more rust
+        registrar.reaction(&ReactionDef {
+            element: rust,
+            with: CElement::of(&crate::variant_type::IRON),
+            chance: 1,
+            becomes: rust,
+            other_becomes: rust,
+        });
+    }
+
+    extern "C" fn bad_register(registrar: *mut Registrar) {
+        let registrar = unsafe { &mut *registrar };
+        registrar.reaction(&ReactionDef {
+            element: CElement::of(&crate::variant_type::SAND),
+            with: CElement::of(&crate::variant_type::SAND),
+            chance: 1,
+            becomes: CElement::of(&EMPTY),
+            other_becomes: CElement::of(&EMPTY),
+        });
+    }
+
+    extern "C" fn bad_field(registrar: *mut Registrar) {
+        let registrar = unsafe { &mut *registrar };
+        registrar.field(
+            CElement::of(&crate::variant_type::SAND),
+            c"grit",
+            FieldKind::U8,
+        );
+    }
+
+    #[test]
+    fn test_install_plugin() {
+        let mut world = World::new(3, 4);
+        let elements = install(&mut world, PLUGIN_ABI_VERSION, register, None).unwrap();
+        let rust = elements[0];
+        assert_eq!(world.find_element("RUST"), Some(rust));
+
+        // its update function moves it down like a powder
+        world.set_element(1, 0, rust);
+        world.tick();
+        let fallen = world.get_particle(1, 1);
+        assert_eq!(fallen.variant_type, rust);
+        assert_eq!(world.get_u8(&fallen, "fallen"), 1);
+
+        // and the reaction spreads it through iron
+        world.set_particle(1, 2, Variant::IRON);
+        world.set_particle(1, 3, Variant::IRON);
+        for _ in 0..200 {
+            world.tick();
+        }
+        assert_eq!(world.get_particle(1, 3).variant_type, rust);
+    }
+
+    // heats the air two cells to the right, and tries to reach further
+    extern "C" fn heater_update(host: *mut HostApi, _me: CParticle) -> bool {
+        let host = unsafe { &mut *host };
+        (host.set_temperature)(host.ctx, 2, 0, 500.);
+        (host.set_temperature)(host.ctx, 3, 0, 500.);
+        true
+    }
+
+    extern "C" fn register_heater(registrar: *mut Registrar) {
+        let registrar = unsafe { &mut *registrar };
+        registrar.element(&ElementDef {
+            name: c"Heater".as_ptr(),
+            code: c"HEAT".as_ptr(),
+            color: [200, 40, 20, 255],
+            weight: 200,
+            strength: 0,
+            property: VariantProperty::Solid.to_u8(),
+            flags: 0,
+            base_temperature: 22.,
+            update: Some(heater_update),
+        });
+    }
+
+    #[test]
+    fn test_temperature_reach() {
+        let mut world = World::new(5, 1);
+        let heater = install(&mut world, PLUGIN_ABI_VERSION, register_heater, None).unwrap()[0];
+        world.set_element(0, 0, heater);
+        world.set_temperature(3, 0, 22.);
+        world.tick();
+        assert_eq!(world.get_temperature(2, 0), 500.);
+        assert_eq!(world.get_temperature(3, 0), 22.);
+    }
+
+    #[test]
+    fn test_version_and_ownership_checks() {
+        let mut world = World::new(3, 3);
+        assert!(install(&mut world, PLUGIN_ABI_VERSION + 1, register, None).is_err());
+        assert!(world.find_element("RUST").is_none());
+        assert!(install(&mut world, PLUGIN_ABI_VERSION, bad_register, None).is_err());
+        assert!(install(&mut world, PLUGIN_ABI_VERSION, bad_field, None).is_err());
+    }
+}
@@ -1,6 +1,13 @@
 // prelude file
 pub use crate::api::*;
+pub use crate::atmosphere::{Gas, GasMix};
+pub use crate::elements::{Behaviour, CustomElement, Elements};
+pub use crate::events::Event;
+pub use crate::gravity::{Gravity, GravityPoint};
+pub use crate::life::{LifeRule, LifeRules};
+pub use crate::metadata::{Field, FieldKind, StateBuffer, Value};
 pub use crate::particle::{particle_to_color, Particle};
+pub use crate::render::{Rect, Renderer, View};
 pub use crate::variant::*;
 pub use crate::variant_type::*;
 pub use crate::world::*;
@@ -0,0 +1,451 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    atmosphere::{self, Gas, DISSOLVE_BELOW},
+    group::ElementInfo,
+    metadata::Value,
+    particle::{Particle, Velocity},
+    solution::{self, BOILING_ELEVATION, BOILING_POINT, SALT_GRAIN, SATURATION},
+    variant::{
+        dissolvable, is_flame, Variant, EDGE_NBRS, EXPLOSIVE_IGNITION, FIRE_O2_USE, LAVA_SOLIDIFY,
+        LIFE_BREATH, LIFE_HEAT_DEATH, OIL_IGNITION, OXGN_IGNITION, PLANT_IGNITION, SAND_MELT,
+        STEAM_CONDENSE, STONE_MELT,
+    },
+    variant_type::{flag_names, VariantProperty, FLAG_BURNS, FLAG_EXPLOSIVE},
+    world::World,
+};
+
+// something the cell could do on its next update, given what is around it now.
+// most of these are chances rather than certainties, and behaviours of scripted or
+// plugin elements aren't known here
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reaction {
+    // what comes out, in place of the cell or of the neighbour it reacts with
+    pub product: Variant,
+    // the neighbour taking part, None when the cell changes by itself
+    pub with: Option<Variant>,
+    pub cause: &'static str,
+}
+
+// what is at a cell and why, for inspectors and tooltips
+#[derive(Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub x: i32,
+    pub y: i32,
+    pub name: String,
+    pub code: String,
+    pub variant: Variant,
+    pub property: VariantProperty,
+    pub flags: Vec<&'static str>,
+    pub temperature: f32,
+    // the air around the particle, which it drifts towards
+    pub ambient: f32,
+    pub pressure: f32,
+    pub velocity: Velocity,
+    pub strength: u8,
+    pub ra: u8,
+    pub rb: u8,
+    pub clock: u8,
+    // the element's state, "life" counts the ticks left for elements that decay
+    pub fields: Vec<(String, Value)>,
+    pub reactions: Vec<Reaction>,
+}
+
+// None outside the world
+pub fn probe(world: &World, x: i32, y: i32) -> Option<Probe> {
+    if x < 0 || x >= world.width || y < 0 || y >= world.height {
+        return None;
+    }
+    let particle = world.get_particle(x, y);
+    let info = ElementInfo::of(&world.elements, particle.variant_type);
+    Some(Probe {
+        x,
+        y,
+        name: info.name,
+        code: info.code,
+        variant: particle.get_variant(),
+        property: particle.variant_type.variant_property,
+        flags: flag_names(particle.variant_type.flags),
+        temperature: particle.temperature,
+        ambient: world.get_temperature(x, y),
+        pressure: world.get_pressure(x, y),
+        velocity: particle.velocity,
+        strength: particle.strength,
+        ra: particle.ra,
+        rb: particle.rb,
+        clock: particle.clock,
+        fields: world.fields(&particle),
+        reactions: reactions(world, x, y, &particle),
+    })
+}
+
+// mirrors the checks in the update functions of `variant`, `solution` and `atmosphere`
+pub fn reactions(world: &World, x: i32, y: i32, particle: &Particle) -> Vec<Reaction> {
+    let mut found = vec![];
+    let mut add = |product, with, cause| {
+        let reaction = Reaction {
+            product,
+            with,
+            cause,
+        };
+        if !found.contains(&reaction) {
+            found.push(reaction);
+        }
+    };
+    let nbrs: Vec<Particle> = (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(|&d| d != (0, 0))
+        .map(|(dx, dy)| world.nbr_at(x + dx, y + dy))
+        .collect();
+    let touches = |pred: fn(&Particle) -> bool| nbrs.iter().any(pred);
+    let temperature = particle.temperature;
+    let oxygen = || atmosphere::richest(world, x, y, Gas::O2).map_or(0., |(_, o2)| o2);
+
+    let variant = particle.get_variant();
+    match variant {
+        Variant::Sand if temperature > SAND_MELT => add(Variant::Glass, None, "melting"),
+        Variant::Stone if temperature > STONE_MELT => add(Variant::Lava, None, "melting"),
+        Variant::Ice if temperature > 0. => add(Variant::Water, None, "melting"),
+        Variant::WTVP if temperature > 0. && temperature < STEAM_CONDENSE => {
+            add(Variant::Water, None, "condensing")
+        }
+        Variant::Lava => {
+            if temperature < LAVA_SOLIDIFY {
+                add(Variant::Stone, None, "cooling");
+            }
+            for nbr in &nbrs {
+                let v = nbr.get_variant();
+                if nbr.variant_type.has_flag(FLAG_BURNS)
+                    && !matches!(
+                        v,
+                        Variant::Empty | Variant::Wall | Variant::Lava | Variant::Fire
+                    )
+                {
+                    add(Variant::Fire, Some(v), "setting alight");
+                }
+            }
+        }
+        Variant::Water | Variant::SaltWater => {
+            let held = solution::concentration(world, particle);
+            if temperature > BOILING_POINT + BOILING_ELEVATION * held {
+                add(Variant::WTVP, None, "boiling");
+            }
+            if held >= SATURATION + SALT_GRAIN {
+                add(Variant::Salt, None, "precipitating");
+            }
+        }
+        Variant::Salt => {
+            for nbr in &nbrs {
+                let held = solution::concentration(world, nbr);
+                if solution::is_solvent(nbr) && held + SALT_GRAIN <= SATURATION {
+                    add(Variant::SaltWater, Some(nbr.get_variant()), "dissolving");
+                }
+            }
+        }
+        Variant::Oil | Variant::Plant => {
+            let ignition = match variant {
+                Variant::Oil => OIL_IGNITION,
+                _ => PLANT_IGNITION,
+            };
+            if temperature > ignition || touches(is_flame) {
+                add(Variant::Fire, None, "burning");
+            }
+            if variant == Variant::Plant && touches(|p| p.get_variant() == Variant::Water) {
+                add(Variant::Plant, Some(Variant::Water), "growing");
+            }
+        }
+        Variant::Acid => {
+            for nbr in nbrs.iter().filter(|p| dissolvable(p)) {
+                add(Variant::Empty, Some(nbr.get_variant()), "dissolving");
+            }
+        }
+        Variant::OXGN => {
+            // oxygen only reacts with the coldest of its four edge neighbours
+            let coldest = EDGE_NBRS
+                .into_iter()
+                .map(|(dx, dy)| world.nbr_at(x + dx, y + dy))
+                .min_by(|a, b| a.temperature.total_cmp(&b.temperature));
+            if coldest.is_some_and(|p| p.get_variant() == Variant::HYGN) {
+                add(Variant::Water, Some(Variant::HYGN), "combining");
+            }
+            if world.get_temperature(x, y) > OXGN_IGNITION {
+                add(Variant::Fire, None, "burning");
+            }
+        }
+        Variant::Fire if world.atmosphere && oxygen() < FIRE_O2_USE * 0.5 => {
+            add(Variant::Smoke, None, "smothered")
+        }
+        Variant::GOL | Variant::HLIF | Variant::SEED | Variant::DNGT => {
+            let same = nbrs.iter().filter(|p| p.get_variant() == variant).count();
+            if let Some(rule) = world.life_rules.get(variant) {
+                if !rule.survives(same as u32) {
+                    add(Variant::Empty, None, "dying out");
+                }
+            }
+            if world.atmosphere && oxygen() < LIFE_BREATH {
+                add(Variant::Empty, None, "suffocating");
+            }
+            if temperature > LIFE_HEAT_DEATH {
+                add(Variant::Sand, None, "overheating");
+            }
+        }
+        _ => (),
+    }
+
+    let explosive = particle.variant_type.has_flag(FLAG_EXPLOSIVE);
+    if explosive && (world.get_temperature(x, y) > EXPLOSIVE_IGNITION || touches(is_flame)) {
+        add(Variant::Fire, None, "exploding");
+    }
+    if let Some(gas) = Gas::from_variant(variant) {
+        if world.atmosphere && world.get_gas(x, y).get(gas) < DISSOLVE_BELOW {
+            add(Variant::Empty, None, "dissolving into the air");
+        }
+    }
+    if let Some(lifetime) = particle.variant_type.lifetime {
+        add(lifetime.product, None, "decaying");
+    }
+    found
+}
+
+impl Display for Reaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.with {
+            Some(with) => write!(
+                f,
+                "{} with {} -> {}",
+                self.cause,
+                with.get_name(),
+                self.product.get_name()
+            ),
+            None => write!(f, "{} -> {}", self.cause, self.product.get_name()),
+        }
+    }
+}
+
+// one field a line, as shown in the tooltip
+impl Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({}) at {}, {}", self.name, self.code, self.x, self.y)?;
+        writeln!(f, "{:?}, flags: {}", self.property, self.flags.join(" "))?;
+        writeln!(
+            f,
+            "temperature {:.1} (ambient {:.1}), pressure {:.2}",
+            self.temperature, self.ambient, self.pressure
+        )?;
+        writeln!(
+            f,
+            "velocity {:.2}, {:.2}, strength {}",
+            self.velocity.x, self.velocity.y, self.strength
+        )?;
+        write!(f, "ra {}, rb {}, clock {}", self.ra, self.rb, self.clock)?;
+        for (name, value) in &self.fields {
+            write!(f, ", {} {}", name, value)?;
+        }
+        for reaction in &self.reactions {
+            write!(f, "\n{}", reaction)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{atmosphere::GasMix, rng};
+
+    #[test]
+    fn test_probe() {
+        let mut world = World::new(4, 4);
+        assert!(probe(&world, 4, 0).is_none());
+
+        world.set_particle(1, 1, Variant::Acid);
+        world.set_particle(2, 1, Variant::Sand);
+        world.set_particle(1, 2, Variant::Glass);
+        let acid = probe(&world, 1, 1).unwrap();
+        assert_eq!((acid.name.as_str(), acid.code.as_str()), ("Acid", "ACID"));
+        assert_eq!(acid.property, VariantProperty::Liquid);
+        // acid eats the sand, but not the glass it's kept in
+        assert_eq!(
+            acid.reactions,
+            vec![Reaction {
+                product: Variant::Empty,
+                with: Some(Variant::Sand),
+                cause: "dissolving",
+            }]
+        );
+        assert!(acid.to_string().ends_with("dissolving with SAND -> EMPT"));
+
+        let sand = probe(&world, 2, 1).unwrap();
+        assert!(sand.reactions.is_empty());
+        let mut hot = world.get_particle(2, 1);
+        hot.temperature = SAND_MELT + 1.;
+        assert_eq!(reactions(&world, 2, 1, &hot)[0].product, Variant::Glass);
+    }
+
+    // a world with `variant` at (2, 2) and `nbr` beside it
+    fn setup(variant: Variant, nbr: Option<Variant>) -> World {
+        let mut world = World::new(5, 5);
+        world.set_particle(2, 2, variant);
+        if let Some(nbr) = nbr {
+            world.set_particle(3, 2, nbr);
+        }
+        world
+    }
+
+    fn heat(world: &mut World, temperature: f32) {
+        let idx = world.get_idx(2, 2);
+        world.particles[idx].temperature = temperature;
+    }
+
+    fn fill(world: &mut World, rows: std::ops::Range<i32>, variant: Variant) {
+        for x in 0..world.width {
+            for y in rows.clone() {
+                world.set_particle(x, y, variant);
+            }
+        }
+    }
+
+    fn without_air(world: &mut World) {
+        world.set_atmosphere(true);
+        for env in world.environment.iter_mut() {
+            env.gas = GasMix::default();
+        }
+    }
+
+    // the probe predicts the reaction at (2, 2), and a seeded run then shows it: there
+    // is more of the product, or less of what reacted when the product is nothing
+    fn happens(mut world: World, product: Variant, with: Option<Variant>, cause: &'static str) {
+        let particle = world.get_particle(2, 2);
+        let expected = Reaction {
+            product,
+            with,
+            cause,
+        };
+        let predicted = reactions(&world, 2, 2, &particle);
+        assert!(
+            predicted.contains(&expected),
+            "{} not in {:?}",
+            cause,
+            predicted
+        );
+
+        let count = |world: &World, variant| {
+            world
+                .particles
+                .iter()
+                .filter(|p| p.get_variant() == variant)
+                .count()
+        };
+        let reactant = with.unwrap_or(particle.get_variant());
+        let (reactants, products) = (count(&world, reactant), count(&world, product));
+        rng::seed(47);
+        for _ in 0..200 {
+            world.tick();
+            let happened = match product {
+                Variant::Empty => count(&world, reactant) < reactants,
+                _ => count(&world, product) > products,
+            };
+            if happened {
+                return;
+            }
+        }
+        panic!("{} was predicted but didn't happen", cause);
+    }
+
+    #[test]
+    fn test_predictions_happen() {
+        use Variant::*;
+
+        let mut world = setup(Sand, None);
+        heat(&mut world, SAND_MELT + 200.);
+        happens(world, Glass, None, "melting");
+
+        let mut world = setup(Stone, None);
+        heat(&mut world, STONE_MELT + 200.);
+        happens(world, Lava, None, "melting");
+
+        let mut world = setup(Ice, None);
+        heat(&mut world, 5.);
+        happens(world, Water, None, "melting");
+
+        let mut world = setup(WTVP, None);
+        heat(&mut world, STEAM_CONDENSE - 50.);
+        happens(world, Water, None, "condensing");
+
+        let mut world = setup(Lava, None);
+        heat(&mut world, LAVA_SOLIDIFY - 50.);
+        happens(world, Stone, None, "cooling");
+
+        happens(setup(Lava, Some(Oil)), Fire, Some(Oil), "setting alight");
+
+        let mut world = setup(Water, None);
+        heat(&mut world, BOILING_POINT + 20.);
+        happens(world, WTVP, None, "boiling");
+
+        let mut world = setup(Water, None);
+        let idx = world.get_idx(2, 2);
+        let held = SATURATION + 2. * SALT_GRAIN;
+        let water = world.particles[idx];
+        world.particles[idx] = solution::with_concentration(&mut world, water, held);
+        happens(world, Salt, None, "precipitating");
+
+        happens(
+            setup(Salt, Some(Water)),
+            SaltWater,
+            Some(Water),
+            "dissolving",
+        );
+
+        let mut world = setup(Oil, None);
+        heat(&mut world, OIL_IGNITION + 50.);
+        happens(world, Fire, None, "burning");
+
+        let mut world = setup(Plant, None);
+        heat(&mut world, PLANT_IGNITION + 50.);
+        happens(world, Fire, None, "burning");
+
+        // a pool the plant sits on, so the water can't flow away from it
+        let mut world = setup(Plant, None);
+        fill(&mut world, 3..5, Water);
+        happens(world, Plant, Some(Water), "growing");
+
+        happens(setup(Acid, Some(Sand)), Empty, Some(Sand), "dissolving");
+
+        // walled in with the hydrogen, which is the coldest thing around
+        let mut world = World::new(5, 5);
+        fill(&mut world, 0..5, Wall);
+        world.erase_indestructible(2, 2);
+        world.erase_indestructible(3, 2);
+        world.set_particle(2, 2, OXGN);
+        world.set_particle(3, 2, HYGN);
+        let idx = world.get_idx(3, 2);
+        world.particles[idx].temperature = -50.;
+        happens(world, Water, Some(HYGN), "combining");
+
+        let mut world = setup(OXGN, None);
+        for env in world.environment.iter_mut() {
+            env.ambient_temperature = OXGN_IGNITION + 50.;
+        }
+        happens(world, Fire, None, "burning");
+
+        let mut world = setup(Fire, None);
+        without_air(&mut world);
+        happens(world, Smoke, None, "smothered");
+
+        happens(setup(GOL, None), Empty, None, "dying out");
+
+        let mut world = setup(GOL, None);
+        without_air(&mut world);
+        happens(world, Empty, None, "suffocating");
+
+        let mut world = setup(GOL, None);
+        heat(&mut world, LIFE_HEAT_DEATH + 50.);
+        happens(world, Sand, None, "overheating");
+
+        happens(setup(HYGN, Some(Fire)), Fire, None, "exploding");
+
+        let mut world = setup(OXGN, None);
+        world.set_atmosphere(true);
+        happens(world, Empty, None, "dissolving into the air");
+    }
+}
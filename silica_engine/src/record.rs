@@ -0,0 +1,268 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{
+    render::{upscale, Renderer, View},
+    world::World,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Apng,
+    // needs the `gif` feature
+    Gif,
+}
+
+// how to record a run, the first frame is the world as it is before any ticks
+#[derive(Clone, Copy, Debug)]
+pub struct Recording {
+    pub format: Format,
+    // ticks to simulate
+    pub ticks: u32,
+    // keep one frame every this many ticks
+    pub frame_skip: u32,
+    // each cell becomes a scale x scale block
+    pub scale: u32,
+    // keep the alpha channel, otherwise frames are opaque like `World::save`
+    pub alpha: bool,
+    pub view: View,
+    pub glow: bool,
+    pub frame_delay_ms: u16,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            format: Format::Apng,
+            ticks: 120,
+            frame_skip: 2,
+            scale: 2,
+            alpha: false,
+            view: View::Normal,
+            glow: true,
+            frame_delay_ms: 40,
+        }
+    }
+}
+
+impl Recording {
+    // the last step is shorter when the ticks don't divide evenly, so every tick is shown
+    pub fn frame_count(&self) -> u32 {
+        self.ticks.div_ceil(self.frame_skip.max(1)) + 1
+    }
+}
+
+// runs the world for `recording.ticks` ticks and writes the animation, returns the frame count.
+// the world is left where the run ended
+pub fn record<W: Write>(
+    world: &mut World,
+    recording: &Recording,
+    writer: W,
+) -> Result<u32, String> {
+    let scale = recording.scale.max(1);
+    let width = world.width as u32 * scale;
+    let height = world.height as u32 * scale;
+    let mut renderer = Renderer::new(world.width, world.height);
+    renderer.set_view(recording.view);
+    renderer.set_glow(recording.glow);
+
+    let mut frames = Frames {
+        world,
+        renderer,
+        recording,
+        frame: vec![],
+        ticked: false,
+        left: recording.ticks,
+    };
+    match recording.format {
+        Format::Apng => write_apng(&mut frames, width, height, writer)?,
+        Format::Gif => write_gif(&mut frames, width, height, writer)?,
+    }
+    Ok(recording.frame_count())
+}
+
+pub fn record_to_file(world: &mut World, recording: &Recording, path: &str) -> Result<u32, String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    record(world, recording, BufWriter::new(file))
+}
+
+// scaled frames of a run, RGBA or RGB without alpha, ticking the world in between
+struct Frames<'a> {
+    world: &'a mut World,
+    renderer: Renderer,
+    recording: &'a Recording,
+    frame: Vec<u8>,
+    ticked: bool,
+    // ticks still to run
+    left: u32,
+}
+
+impl Frames<'_> {
+    fn next(&mut self) -> &mut Vec<u8> {
+        if self.ticked {
+            let step = self.recording.frame_skip.max(1).min(self.left);
+            for _ in 0..step {
+                self.world.tick();
+            }
+            self.left -= step;
+        }
+        self.ticked = true;
+
+        self.renderer.update(self.world);
+        upscale(
+            self.renderer.pixels(),
+            self.world.width as usize,
+            self.recording.scale.max(1) as usize,
+            &mut self.frame,
+        );
+        if !self.recording.alpha {
+            self.frame = self
+                .frame
+                .chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]])
+                .collect();
+        }
+        &mut self.frame
+    }
+}
+
+fn write_apng<W: Write>(
+    frames: &mut Frames,
+    width: u32,
+    height: u32,
+    writer: W,
+) -> Result<(), String> {
+    let recording = frames.recording;
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(match recording.alpha {
+        true => png::ColorType::Rgba,
+        false => png::ColorType::Rgb,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(recording.frame_count(), 0)
+        .and_then(|_| encoder.set_frame_delay(recording.frame_delay_ms, 1000))
+        .map_err(|e| e.to_string())?;
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    for _ in 0..recording.frame_count() {
+        writer
+            .write_image_data(frames.next())
+            .map_err(|e| e.to_string())?;
+    }
+    writer.finish().map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "gif")]
+fn write_gif<W: Write>(
+    frames: &mut Frames,
+    width: u32,
+    height: u32,
+    writer: W,
+) -> Result<(), String> {
+    let recording = frames.recording;
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => return Err(format!("{}x{} is too big for a gif", width, height)),
+    };
+    let mut encoder = gif::Encoder::new(writer, width, height, &[]).map_err(|e| e.to_string())?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(|e| e.to_string())?;
+
+    for _ in 0..recording.frame_count() {
+        // gif delays are in hundredths of a second
+        let mut frame = match recording.alpha {
+            true => gif::Frame::from_rgba_speed(width, height, frames.next(), 10),
+            false => gif::Frame::from_rgb_speed(width, height, frames.next(), 10),
+        };
+        frame.delay = recording.frame_delay_ms / 10;
+        encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "gif"))]
+fn write_gif<W: Write>(_: &mut Frames, _: u32, _: u32, _: W) -> Result<(), String> {
+    Err("gif export needs the `gif` feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Variant;
+
+    #[test]
+    fn test_record_apng() {
+        let mut world = World::new(8, 8);
+        world.set_particle(4, 0, Variant::Sand);
+        let recording = Recording {
+            ticks: 6,
+            frame_skip: 2,
+            scale: 3,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        assert_eq!(record(&mut world, &recording, &mut out), Ok(4));
+
+        // the sand fell while recording, and the file reads back as 4 frames of 24x24
+        assert_ne!(world.get_particle(4, 0).get_variant(), Variant::Sand);
+        let decoder = png::Decoder::new(out.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (24, 24));
+        assert_eq!(info.animation_control.unwrap().num_frames, 4);
+
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+
+        let mut first = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut first).unwrap();
+        // the grain at (4, 0) fills a 3x3 block on black
+        let sand = |x: usize, y: usize| first[(x + y * 24) * 3..][..3] != [0, 0, 0];
+        assert!(sand(12, 0) && sand(14, 2) && !sand(15, 0) && !sand(12, 3));
+    }
+
+    #[test]
+    fn test_record_every_tick() {
+        let mut world = World::new(8, 8);
+        world.set_particle(4, 0, Variant::Sand);
+        // frames after 0, 2, 4 and the odd fifth tick
+        let recording = Recording {
+            ticks: 5,
+            frame_skip: 2,
+            scale: 1,
+            alpha: true,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        assert_eq!(record(&mut world, &recording, &mut out), Ok(4));
+        assert_eq!(world.ticks, 5);
+
+        let decoder = png::Decoder::new(out.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().animation_control.unwrap().num_frames, 4);
+        assert_eq!(reader.info().color_type, png::ColorType::Rgba);
+        let mut frame = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut frame).unwrap();
+        // empty cells stay see-through
+        assert_eq!(frame[3], 0);
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn test_record_gif() {
+        let mut world = World::new(8, 8);
+        world.set_particle(4, 0, Variant::Sand);
+        let recording = Recording {
+            format: Format::Gif,
+            ticks: 4,
+            frame_skip: 1,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        assert_eq!(record(&mut world, &recording, &mut out), Ok(5));
+        assert!(out.starts_with(b"GIF89a"));
+    }
+}
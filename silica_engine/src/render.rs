@@ -0,0 +1,774 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    colors::{DRAPER_POINT, HIGH_PRESSURE_COLOR, LOW_PRESSURE_COLOR, MODIFIED_COLOR},
+    metadata::META_BYTES,
+    particle::{particle_to_color, Particle},
+    variant::Variant,
+    variant_type::{incandescence, Emission, ParticleColor, HSV},
+    world::World,
+};
+
+// dirty regions are tracked in square tiles of this many cells
+pub const TILE: i32 = 16;
+
+// the heat view runs from blue at HEAT_MIN to red at HEAT_MAX
+pub const HEAT_MIN: f32 = -50.;
+pub const HEAT_MAX: f32 = 1500.;
+// pressure away from ambient and speed at which those views are fully saturated
+pub const PRESSURE_RANGE: f32 = 10.;
+// a particle moves at most two cells a tick
+pub const VELOCITY_RANGE: f32 = 2.;
+
+// what the renderer draws
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    Normal,
+    // particle temperature, ambient temperature for empty cells
+    Heat,
+    // red above ambient, blue below
+    Pressure,
+    // hue is the direction of the last step, brightness its length
+    Velocity,
+    // a distinct colour per element, runtime ones included
+    Element,
+    // the normal view dimmed, cells updated last tick highlighted
+    Modified,
+}
+
+impl View {
+    pub fn from_u8(val: u8) -> View {
+        match val {
+            0 => View::Normal,
+            1 => View::Heat,
+            2 => View::Pressure,
+            3 => View::Velocity,
+            4 => View::Element,
+            5 => View::Modified,
+            _ => View::Normal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+// everything particle_to_color reads, a cell is only redrawn when this changes.
+// nothing glows below the draper point, and above it a degree is too little to see
+#[derive(Clone, Copy, PartialEq)]
+struct Look {
+    color: ParticleColor,
+    variant: Variant,
+    ra: u8,
+    strength: u8,
+    temperature: i32,
+    // the element state by value, handles into the state buffer get reused
+    state: [u8; META_BYTES],
+}
+
+impl Look {
+    fn of(world: &World, particle: &Particle) -> Look {
+        Look {
+            color: particle.variant_type.color,
+            variant: particle.get_variant(),
+            ra: particle.ra,
+            strength: particle.strength,
+            temperature: particle.temperature.max(DRAPER_POINT) as i32,
+            state: world.state.pack(&world.elements, particle),
+        }
+    }
+}
+
+// the light a particle gives off right now, None for things that don't glow
+pub fn emission(particle: &Particle) -> Option<Emission> {
+    let emission = particle.variant_type.emission?;
+    if !emission.incandescent {
+        return Some(emission);
+    }
+    let color = incandescence(particle.temperature);
+    (color.a > 0).then_some(Emission {
+        color,
+        intensity: color.a as f32 / 255. * emission.intensity,
+        ..emission
+    })
+}
+
+// keeps an RGBA8 image of the world and redraws only the cells that changed,
+// so a frontend uploads the dirty rects instead of the whole frame. the normal
+// view only looks at the world's modified cells, the debug views at every cell
+pub struct Renderer {
+    width: i32,
+    height: i32,
+    view: View,
+    // additive light around emissive elements, normal view only
+    glow: bool,
+    pixels: Vec<u8>,
+    // colours before the glow is added
+    base: Vec<[u8; 4]>,
+    // what the normal view last drew, None until then
+    drawn: Vec<Option<Look>>,
+    // glow added to each cell, and the cells that got any
+    light: Vec<[f32; 3]>,
+    lit: Vec<usize>,
+    // cells that give off light, by index so the light is spread in the same order
+    emitters: BTreeMap<usize, Emission>,
+    // an emitter came, went or changed since the light was last spread
+    relight: bool,
+    // cells to composite again this update
+    stale: Vec<usize>,
+    // every cell counts as changed on the next update
+    redraw: bool,
+    // the world's tick and edit counts at the last update
+    seen: Option<(u64, u64)>,
+    tiles: Vec<bool>,
+    dirty: Vec<Rect>,
+}
+
+impl Renderer {
+    pub fn new(width: i32, height: i32) -> Renderer {
+        let cells = (width * height) as usize;
+        Renderer {
+            width,
+            height,
+            view: View::Normal,
+            glow: true,
+            pixels: vec![0; cells * 4],
+            base: vec![[0; 4]; cells],
+            drawn: vec![None; cells],
+            light: vec![[0.; 3]; cells],
+            lit: Vec::new(),
+            emitters: BTreeMap::new(),
+            relight: false,
+            stale: Vec::new(),
+            redraw: true,
+            seen: None,
+            tiles: vec![false; (tiles(width) * tiles(height)) as usize],
+            dirty: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    // row major, 4 bytes a cell
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    // what the last update redrew
+    pub fn dirty(&self) -> &[Rect] {
+        &self.dirty
+    }
+
+    // redraw everything on the next update
+    pub fn invalidate(&mut self) {
+        self.drawn.fill(None);
+        self.redraw = true;
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    pub fn set_view(&mut self, view: View) {
+        if view != self.view {
+            self.view = view;
+            self.invalidate();
+        }
+    }
+
+    pub fn glow(&self) -> bool {
+        self.glow
+    }
+
+    pub fn set_glow(&mut self, glow: bool) {
+        if glow != self.glow {
+            self.glow = glow;
+            self.invalidate();
+        }
+    }
+
+    // bring the image up to date with the world, returns the regions that changed
+    pub fn update(&mut self, world: &World) -> &[Rect] {
+        if (world.width, world.height) != (self.width, self.height) {
+            let (view, glow) = (self.view, self.glow);
+            *self = Renderer::new(world.width, world.height);
+            self.view = view;
+            self.glow = glow;
+        }
+
+        let glow = self.glow && self.view == View::Normal;
+        if self.redraw || self.view != View::Normal || world.cleared || !self.caught_up(world) {
+            self.emitters.clear();
+            self.relight = true;
+            for idx in 0..self.drawn.len() {
+                self.draw(world, idx, glow);
+            }
+        } else {
+            for &idx in &world.modified_indices {
+                self.draw(world, idx, glow);
+            }
+        }
+        self.seen = Some((world.ticks, world.edits));
+        if self.relight {
+            self.spread_light();
+        }
+
+        self.tiles.fill(false);
+        let tiles_x = tiles(self.width);
+        for idx in std::mem::take(&mut self.stale) {
+            let [r, g, b, a] = self.base[idx];
+            let light = self.light[idx];
+            let add = |c: u8, l: f32| (c as f32 + l * 255.).min(255.) as u8;
+            // light shows over empty cells too
+            let alpha = (light[0].max(light[1]).max(light[2]) * 255.).min(255.) as u8;
+            let color = [
+                add(r, light[0]),
+                add(g, light[1]),
+                add(b, light[2]),
+                a.max(alpha),
+            ];
+
+            let pixel = &mut self.pixels[idx * 4..idx * 4 + 4];
+            if pixel == color && !self.redraw {
+                continue;
+            }
+            pixel.copy_from_slice(&color);
+            let (x, y) = (idx as i32 % self.width, idx as i32 / self.width);
+            self.tiles[(x / TILE + y / TILE * tiles_x) as usize] = true;
+        }
+
+        self.redraw = false;
+        self.collect_dirty();
+        &self.dirty
+    }
+
+    // whether the world's modified cells hold every change since the last update,
+    // they are cleared when a tick starts so edits made before it are lost
+    fn caught_up(&self, world: &World) -> bool {
+        match self.seen {
+            Some((ticks, edits)) => {
+                world.ticks == ticks || (world.ticks == ticks + 1 && world.edits_at_tick == edits)
+            }
+            None => false,
+        }
+    }
+
+    fn draw(&mut self, world: &World, idx: usize, glow: bool) {
+        let particle = world.particles[idx];
+        if glow {
+            let emission = emission(&particle);
+            if self.emitters.get(&idx) != emission.as_ref() {
+                match emission {
+                    Some(emission) => self.emitters.insert(idx, emission),
+                    None => self.emitters.remove(&idx),
+                };
+                self.relight = true;
+            }
+        }
+
+        // the debug views are cheap enough to work out for every cell
+        let color = match self.view {
+            View::Normal => {
+                let look = Some(Look::of(world, &particle));
+                if self.drawn[idx] == look {
+                    return;
+                }
+                self.drawn[idx] = look;
+                particle_to_color(world, particle)
+            }
+            view => view_color(view, world, idx),
+        };
+
+        let (r, g, b, a) = color.to_rgba8();
+        if self.base[idx] == [r, g, b, a] && !self.redraw {
+            return;
+        }
+        self.base[idx] = [r, g, b, a];
+        self.stale.push(idx);
+    }
+
+    // cells lit last update or this one have to be composited again
+    fn spread_light(&mut self) {
+        self.relight = false;
+        for idx in self.lit.drain(..) {
+            self.light[idx] = [0.; 3];
+            self.stale.push(idx);
+        }
+        for (&idx, emission) in &self.emitters {
+            let (x, y) = (idx as i32 % self.width, idx as i32 / self.width);
+            let r = emission.radius;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let (nx, ny) = (x + dx, y + dy);
+                    let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                    if nx < 0 || nx >= self.width || ny < 0 || ny >= self.height {
+                        continue;
+                    }
+                    if distance > r as f32 {
+                        continue;
+                    }
+
+                    let falloff = (1. - distance / (r + 1) as f32).powi(2) * emission.intensity;
+                    let idx = (nx + ny * self.width) as usize;
+                    let light = &mut self.light[idx];
+                    if *light == [0.; 3] {
+                        self.lit.push(idx);
+                    }
+                    light[0] += emission.color.r as f32 / 255. * falloff;
+                    light[1] += emission.color.g as f32 / 255. * falloff;
+                    light[2] += emission.color.b as f32 / 255. * falloff;
+                }
+            }
+        }
+        self.stale.extend_from_slice(&self.lit);
+    }
+
+    // runs of dirty tiles along a row become one rect, which grows down over the same run below
+    fn collect_dirty(&mut self) {
+        self.dirty.clear();
+        let tiles_x = tiles(self.width);
+        for ty in 0..tiles(self.height) {
+            let mut tx = 0;
+            while tx < tiles_x {
+                if !self.tiles[(tx + ty * tiles_x) as usize] {
+                    tx += 1;
+                    continue;
+                }
+                let start = tx;
+                while tx < tiles_x && self.tiles[(tx + ty * tiles_x) as usize] {
+                    tx += 1;
+                }
+
+                let rect = self.clip(Rect {
+                    x: start * TILE,
+                    y: ty * TILE,
+                    width: (tx - start) * TILE,
+                    height: TILE,
+                });
+                match self
+                    .dirty
+                    .iter_mut()
+                    .find(|r| r.x == rect.x && r.width == rect.width && r.y + r.height == rect.y)
+                {
+                    Some(above) => above.height += rect.height,
+                    None => self.dirty.push(rect),
+                }
+            }
+        }
+    }
+
+    fn clip(&self, rect: Rect) -> Rect {
+        Rect {
+            width: rect.width.min(self.width - rect.x),
+            height: rect.height.min(self.height - rect.y),
+            ..rect
+        }
+    }
+
+    // the pixels of `rect` packed row after row, for uploading part of a texture
+    pub fn copy_rect(&self, rect: Rect, out: &mut Vec<u8>) {
+        out.clear();
+        for y in rect.y..rect.y + rect.height {
+            let start = ((rect.x + y * self.width) * 4) as usize;
+            out.extend_from_slice(&self.pixels[start..start + rect.width as usize * 4]);
+        }
+    }
+}
+
+fn view_color(view: View, world: &World, idx: usize) -> ParticleColor {
+    let particle = world.particles[idx];
+    let environment = &world.environment[idx];
+    match view {
+        View::Normal => particle_to_color(world, particle),
+        View::Heat => {
+            let temperature = match particle.get_variant() {
+                Variant::Empty => environment.ambient_temperature,
+                _ => particle.temperature,
+            };
+            let t = ((temperature - HEAT_MIN) / (HEAT_MAX - HEAT_MIN)).clamp(0., 1.);
+            HSV {
+                h: 240. * (1. - t),
+                s: 1.,
+                v: 1.,
+            }
+            .hsv_to_rgb()
+        }
+        View::Pressure => {
+            let (x, y) = (idx as i32 % world.width, idx as i32 / world.width);
+            let pressure = world.get_pressure(x, y) - environment.ambient_pressure;
+            let mut color = match pressure < 0. {
+                true => LOW_PRESSURE_COLOR,
+                false => HIGH_PRESSURE_COLOR,
+            };
+            let amount = (pressure.abs() / PRESSURE_RANGE).min(1.);
+            color.darken_by_strength(((1. - amount) * 255.) as u8);
+            color
+        }
+        View::Velocity => {
+            let velocity = particle.velocity;
+            let speed = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
+            HSV {
+                h: velocity.y.atan2(velocity.x).to_degrees().rem_euclid(360.),
+                s: 1.,
+                v: (speed / VELOCITY_RANGE).min(1.),
+            }
+            .hsv_to_rgb()
+        }
+        View::Element => match particle.get_variant() {
+            Variant::Empty => ParticleColor::from_rgba((0, 0, 0, 255)),
+            variant => {
+                // golden angle steps keep neighbouring ids apart
+                let id = variant as u32 + u32::from(particle.variant_type.custom) * 31;
+                HSV {
+                    h: (id as f32 * 137.508) % 360.,
+                    s: 0.7,
+                    v: 0.9,
+                }
+                .hsv_to_rgb()
+            }
+        },
+        View::Modified => {
+            let mut color = particle_to_color(world, particle);
+            color.darken_by_strength(160);
+            color.a = 255;
+            match world.modified_indices.contains(&idx) {
+                true => color.blend(&MODIFIED_COLOR),
+                false => color,
+            }
+        }
+    }
+}
+
+// nearest neighbour upscale of an RGBA image `width` cells wide, into `out`
+pub fn upscale(pixels: &[u8], width: usize, scale: usize, out: &mut Vec<u8>) {
+    out.clear();
+    for row in pixels.chunks_exact(width * 4) {
+        let start = out.len();
+        for pixel in row.chunks_exact(4) {
+            for _ in 0..scale {
+                out.extend_from_slice(pixel);
+            }
+        }
+        for _ in 1..scale {
+            out.extend_from_within(start..start + width * scale * 4);
+        }
+    }
+}
+
+fn tiles(cells: i32) -> i32 {
+    (cells + TILE - 1) / TILE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::API,
+        colors::FIRE_COLOR,
+        variant_type::{VariantProperty, VariantType, CUSTOM, LAVA},
+    };
+
+    #[test]
+    fn test_only_changes_are_redrawn() {
+        let mut world = World::new(40, 20);
+        let mut renderer = Renderer::new(40, 20);
+
+        // the first frame is drawn whole, clipped to the world
+        assert_eq!(
+            renderer.update(&world),
+            &[Rect {
+                x: 0,
+                y: 0,
+                width: 40,
+                height: 20,
+            }]
+        );
+        assert!(renderer.update(&world).is_empty());
+
+        world.set_particle(35, 18, Variant::Wall);
+        assert_eq!(
+            renderer.update(&world),
+            &[Rect {
+                x: 32,
+                y: 16,
+                width: 8,
+                height: 4,
+            }]
+        );
+        let idx = (35 + 18 * 40) * 4;
+        let wall = particle_to_color(&world, world.get_particle(35, 18)).to_rgba8();
+        assert_eq!(
+            renderer.pixels()[idx..idx + 4],
+            [wall.0, wall.1, wall.2, wall.3]
+        );
+
+        let mut rect = vec![];
+        renderer.copy_rect(renderer.dirty()[0], &mut rect);
+        assert_eq!(rect.len(), 8 * 4 * 4);
+        assert_eq!(rect[(3 + 2 * 8) * 4], wall.0);
+    }
+
+    #[test]
+    fn test_follows_the_changed_cells() {
+        let mut world = World::new(32, 32);
+        for x in 4..28 {
+            world.set_particle(x, 4, Variant::Sand);
+            world.set_particle(x, 8, Variant::Water);
+        }
+        world.set_particle(16, 20, Variant::Lava);
+        let mut renderer = Renderer::new(32, 32);
+        renderer.update(&world);
+
+        for i in 0..60 {
+            // painted before the tick clears the modified cells, and after it
+            if i % 10 == 0 {
+                world.set_particle(i / 2, 0, Variant::Fire);
+            }
+            world.tick();
+            if i % 7 == 0 {
+                world.set_particle(i / 2, 30, Variant::Wall);
+            }
+            // two ticks between updates now and then
+            if i % 13 == 0 {
+                world.tick();
+            }
+            renderer.update(&world);
+        }
+
+        // only looking at what changed ends up where a fresh full draw does
+        let mut fresh = Renderer::new(32, 32);
+        fresh.update(&world);
+        assert!(renderer.pixels() == fresh.pixels());
+
+        // a settled world isn't looked at cell by cell
+        let mut world = World::new(32, 32);
+        world.set_particle(3, 31, Variant::Wall);
+        world.tick();
+        renderer.update(&world);
+        world.tick();
+        assert!(world.modified_indices.is_empty());
+        assert!(renderer.caught_up(&world));
+        assert!(renderer.update(&world).is_empty());
+    }
+
+    #[test]
+    fn test_views() {
+        let mut world = World::new(16, 16);
+        world.set_particle(2, 2, Variant::Sand);
+        world.set_particle(4, 4, Variant::Water);
+        world.add_heat(4, 4, 1000.);
+        let mut renderer = Renderer::new(16, 16);
+        renderer.update(&world);
+        let pixel = |renderer: &Renderer, x: usize, y: usize| {
+            let idx = (x + y * 16) * 4;
+            renderer.pixels()[idx..idx + 4].to_vec()
+        };
+
+        // switching views redraws everything
+        renderer.set_view(View::Heat);
+        assert_eq!(renderer.update(&world).len(), 1);
+        let (hot, cold) = (pixel(&renderer, 4, 4), pixel(&renderer, 2, 2));
+        assert!(hot[0] > cold[0] && hot[2] < cold[2]);
+        assert!(renderer.update(&world).is_empty());
+
+        renderer.set_view(View::Element);
+        renderer.update(&world);
+        assert_ne!(pixel(&renderer, 2, 2), pixel(&renderer, 4, 4));
+        assert_eq!(pixel(&renderer, 0, 0), vec![0, 0, 0, 255]);
+
+        // the falling sand is highlighted, the wall it lands on isn't
+        world.set_particle(8, 15, Variant::Wall);
+        world.set_particle(8, 8, Variant::Sand);
+        world.tick();
+        renderer.set_view(View::Modified);
+        renderer.update(&world);
+        assert!(world.modified_indices.contains(&(8 + 8 * 16)));
+        let (sand, wall) = (pixel(&renderer, 8, 8), pixel(&renderer, 8, 15));
+        assert!(sand[0] > wall[0] && sand[1] > wall[1]);
+    }
+
+    #[test]
+    fn test_views_follow_the_world() {
+        let mut world = World::new(8, 8);
+        world.set_particle(2, 2, Variant::Sand);
+        let mut renderer = Renderer::new(8, 8);
+        let pixel = |renderer: &Renderer| renderer.pixels()[(2 + 2 * 8) * 4..][..4].to_vec();
+
+        for view in [View::Heat, View::Pressure, View::Element, View::Modified] {
+            renderer.set_view(view);
+            renderer.update(&world);
+            let before = pixel(&renderer);
+            match view {
+                View::Heat => world.add_heat(2, 2, 500.),
+                View::Pressure => world.set_pressure(2, 2, 5.),
+                View::Element => world.set_particle(2, 2, Variant::Water),
+                _ => world.tick(),
+            }
+            assert!(!renderer.update(&world).is_empty(), "{:?}", view);
+            assert_ne!(pixel(&renderer), before, "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn test_pressure_is_one_field() {
+        let mut world = World::new(4, 4);
+        world.set_pressure(1, 1, 5.);
+        // scripts and plugins raise it through the api
+        API {
+            world: &mut world,
+            x: 2,
+            y: 2,
+        }
+        .set_pressure(2, 2, 5.);
+        let mut renderer = Renderer::new(4, 4);
+        renderer.set_view(View::Pressure);
+        renderer.update(&world);
+
+        for (x, y) in [(1, 1), (2, 2)] {
+            assert_eq!(crate::probe::probe(&world, x, y).unwrap().pressure, 5.);
+            let idx = ((x + y * 4) * 4) as usize;
+            let pixel = &renderer.pixels()[idx..idx + 4];
+            assert!(pixel[0] > pixel[2], "{:?}", pixel);
+        }
+    }
+
+    #[test]
+    fn test_velocity_view() {
+        let mut world = World::new(8, 8);
+        world.set_particle(4, 0, Variant::Sand);
+        world.set_particle(0, 7, Variant::Wall);
+        let mut renderer = Renderer::new(8, 8);
+        renderer.set_view(View::Velocity);
+        let pixel = |renderer: &Renderer, x: i32, y: i32| {
+            let idx = ((x + y * 8) * 4) as usize;
+            renderer.pixels()[idx..idx + 3].to_vec()
+        };
+
+        let sand = |world: &World| {
+            (0..8)
+                .flat_map(|y| (0..8).map(move |x| (x, y)))
+                .find(|&(x, y)| world.get_particle(x, y).get_variant() == Variant::Sand)
+                .unwrap()
+        };
+
+        // the falling grain shows, the wall that never moves doesn't
+        world.tick();
+        renderer.update(&world);
+        let (x, y) = sand(&world);
+        assert!(y > 0);
+        assert_ne!(pixel(&renderer, x, y), vec![0, 0, 0]);
+        assert_eq!(pixel(&renderer, 0, 7), vec![0, 0, 0]);
+
+        // and goes dark once it has landed
+        for _ in 0..10 {
+            world.tick();
+        }
+        renderer.update(&world);
+        let (x, y) = sand(&world);
+        assert_eq!(y, 7);
+        assert_eq!(pixel(&renderer, x, y), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_glow() {
+        // the ramp goes from nothing through red to white
+        assert_eq!(incandescence(20.).a, 0);
+        let (red, yellow) = (incandescence(800.), incandescence(1400.));
+        assert!(red.r > red.g && red.a < yellow.a && yellow.g > red.g);
+        assert_eq!(incandescence(5000.).to_rgba8(), (255, 255, 255, 255));
+
+        let mut world = World::new(16, 16);
+        world.set_particle(8, 15, Variant::Lava);
+        let mut renderer = Renderer::new(16, 16);
+        renderer.update(&world);
+        let pixel = |renderer: &Renderer, x: usize, y: usize| {
+            let idx = (x + y * 16) * 4;
+            renderer.pixels()[idx..idx + 4].to_vec()
+        };
+        // empty space next to the lava is lit, further out it isn't
+        assert!(pixel(&renderer, 8, 13)[0] > 0);
+        assert_eq!(pixel(&renderer, 8, 8), vec![0, 0, 0, 0]);
+
+        // and goes dark again once the lava is gone
+        world.set_particle(8, 15, Variant::Empty);
+        assert!(!renderer.update(&world).is_empty());
+        assert_eq!(pixel(&renderer, 8, 13), vec![0, 0, 0, 0]);
+
+        world.set_particle(8, 15, Variant::Lava);
+        renderer.set_glow(false);
+        renderer.update(&world);
+        assert_eq!(pixel(&renderer, 8, 13), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_emission_comes_from_the_element() {
+        let mut world = World::new(16, 16);
+        let lamp = world.register_element(
+            "Lamp",
+            "LAMP",
+            VariantType {
+                variant_property: VariantProperty::Solid,
+                emission: Some(Emission::new(FIRE_COLOR, 0.5, 3)),
+                ..CUSTOM
+            },
+            None,
+        );
+        world.set_element(8, 8, lamp);
+        world.set_particle(2, 2, Variant::Lava);
+        assert!(emission(&world.get_particle(8, 8)).is_some());
+
+        // lava that doesn't glow any more leaves its neighbours dark
+        world.override_element(VariantType {
+            emission: None,
+            ..LAVA
+        });
+        assert_eq!(emission(&world.get_particle(2, 2)), None);
+        let mut renderer = Renderer::new(16, 16);
+        renderer.update(&world);
+        let lit = |x: usize, y: usize| renderer.pixels()[(x + y * 16) * 4..][..3] != [0, 0, 0];
+        assert!(lit(8, 10) && !lit(2, 4));
+    }
+
+    #[test]
+    fn test_dirty_rects_merge() {
+        let mut world = World::new(64, 64);
+        let mut renderer = Renderer::new(64, 64);
+        renderer.update(&world);
+
+        // a column two tiles tall and a lone tile off to the side
+        world.set_particle(1, 1, Variant::Wall);
+        world.set_particle(1, 20, Variant::Wall);
+        world.set_particle(50, 1, Variant::Wall);
+        let mut dirty = renderer.update(&world).to_vec();
+        dirty.sort_by_key(|r| r.x);
+        assert_eq!(
+            dirty,
+            vec![
+                Rect {
+                    x: 0,
+                    y: 0,
+                    width: 16,
+                    height: 32,
+                },
+                Rect {
+                    x: 48,
+                    y: 0,
+                    width: 16,
+                    height: 16,
+                },
+            ]
+        );
+    }
+}
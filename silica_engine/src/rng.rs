@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// every random choice the simulation makes comes from here. each thread has its own
+// generator, seeded from entropy until `seed` is called, so a seeded run on one thread
+// replays exactly
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// 0..n, 0 when n isn't positive
+pub fn rand_int(n: i32) -> i32 {
+    if n <= 0 {
+        return 0;
+    }
+    RNG.with(|rng| rng.borrow_mut().gen_range(0..n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_runs_repeat() {
+        seed(7);
+        let first: Vec<i32> = (0..16).map(|_| rand_int(1000)).collect();
+        seed(7);
+        let second: Vec<i32> = (0..16).map(|_| rand_int(1000)).collect();
+        assert_eq!(first, second);
+        assert_eq!(rand_int(0), 0);
+    }
+}
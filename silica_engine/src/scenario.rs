@@ -0,0 +1,174 @@
+use std::{fs, path::Path};
+
+use crate::{rng, world::World};
+
+// a small hand written world that replays the same way every time:
+//
+//     # sand poured on a ledge
+//     seed 7
+//     ticks 60
+//     cell # WALL
+//     cell s SAND
+//     map
+//     #....s....#
+//     #.........#
+//     ###########
+//
+// every map row is one row of cells, `.` and space are empty, other characters need a
+// `cell` line naming the element by short code or runtime name. a `double_buffered` line
+// updates automata from the previous tick, as life rules expect
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    pub seed: u64,
+    pub ticks: u32,
+    pub double_buffered: bool,
+    pub legend: Vec<(char, String)>,
+    pub rows: Vec<String>,
+}
+
+impl Scenario {
+    pub fn parse(source: &str) -> Result<Scenario, String> {
+        let mut scenario = Scenario {
+            seed: 0,
+            ticks: 0,
+            double_buffered: false,
+            legend: vec![],
+            rows: vec![],
+        };
+        let mut lines = source.lines().enumerate();
+        for (n, line) in lines.by_ref() {
+            let line = line.trim();
+            let err = |e: &str| format!("line {}: {}", n + 1, e);
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => continue,
+                Some(comment) if comment.starts_with('#') => continue,
+                Some("map") => break,
+                Some("seed") => {
+                    scenario.seed = parse(words.next(), "seed").map_err(|e| err(&e))?;
+                }
+                Some("ticks") => {
+                    scenario.ticks = parse(words.next(), "ticks").map_err(|e| err(&e))?;
+                }
+                Some("double_buffered") => scenario.double_buffered = true,
+                Some("cell") => match (words.next(), words.next()) {
+                    (Some(key), Some(code)) if key.chars().count() == 1 => {
+                        let key = key.chars().next().unwrap();
+                        scenario.legend.push((key, code.to_string()));
+                    }
+                    _ => return Err(err("expected `cell <character> <element>`")),
+                },
+                Some(other) => return Err(err(&format!("unknown setting {:?}", other))),
+            }
+        }
+
+        scenario.rows = lines.map(|(_, row)| row.trim_end().to_string()).collect();
+        while scenario.rows.last().is_some_and(|row| row.is_empty()) {
+            scenario.rows.pop();
+        }
+        if scenario.rows.is_empty() {
+            return Err("the scenario has no map".to_string());
+        }
+        Ok(scenario)
+    }
+
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Scenario::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn width(&self) -> i32 {
+        self.rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0) as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+
+    // seeds the generator, then lays out the map
+    pub fn build(&self) -> Result<World, String> {
+        rng::seed(self.seed);
+        let mut world = World::new(self.width(), self.height());
+        world.set_double_buffered(self.double_buffered);
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, key) in row.chars().enumerate() {
+                if key == '.' || key == ' ' {
+                    continue;
+                }
+                let code = self
+                    .legend
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, code)| code)
+                    .ok_or_else(|| format!("no `cell` line for {:?}", key))?;
+                let variant_type = world
+                    .find_element(code)
+                    .ok_or_else(|| format!("unknown element {:?}", code))?;
+                world.set_element(x as i32, y as i32, variant_type);
+            }
+        }
+        Ok(world)
+    }
+
+    // the world after `ticks` ticks
+    pub fn run(&self) -> Result<World, String> {
+        let mut world = self.build()?;
+        for _ in 0..self.ticks {
+            world.tick();
+        }
+        Ok(world)
+    }
+}
+
+fn parse<T: std::str::FromStr>(word: Option<&str>, name: &str) -> Result<T, String> {
+    word.and_then(|w| w.parse().ok())
+        .ok_or_else(|| format!("expected a number after {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Variant;
+
+    const LEDGE: &str = "
+        # sand poured on a ledge
+        seed 7
+        ticks 12
+        cell # WALL
+        cell s SAND
+        map
+#..sss..#
+#.......#
+#########
+";
+
+    #[test]
+    fn test_scenario() {
+        let scenario = Scenario::parse(LEDGE).unwrap();
+        assert_eq!((scenario.seed, scenario.ticks), (7, 12));
+        assert_eq!((scenario.width(), scenario.height()), (9, 3));
+
+        let world = scenario.build().unwrap();
+        assert_eq!(world.get_particle(4, 0).get_variant(), Variant::Sand);
+        assert_eq!(world.get_particle(0, 2).get_variant(), Variant::Wall);
+
+        // the same seed replays the same run
+        let colors = |world: &World| {
+            (0..world.width * world.height)
+                .map(|i| world.get_particle(i % world.width, i / world.width).ra)
+                .collect::<Vec<_>>()
+        };
+        let first = scenario.run().unwrap();
+        assert_eq!(colors(&first), colors(&scenario.run().unwrap()));
+        let landed = (1..8).filter(|x| first.get_particle(*x, 1).get_variant() == Variant::Sand);
+        assert_eq!(landed.count(), 3);
+
+        assert!(Scenario::parse("cell xx SAND\nmap\nx").is_err());
+        let unknown = Scenario::parse("cell x DUST\nmap\nx").unwrap();
+        assert!(unknown.build().is_err_and(|e| e.contains("DUST")));
+    }
+}
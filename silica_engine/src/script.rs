@@ -0,0 +1,621 @@
+use std::sync::{Arc, Mutex};
+
+use rhai::{
+    module_resolvers::DummyModuleResolver, Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT,
+    INT,
+};
+
+use crate::{
+    api::API,
+    elements::{Behaviour, Elements},
+    group::{ElementInfo, ElementManager},
+    metadata::{self, Field, FieldKind, Value},
+    particle::Particle,
+    rng,
+    variant_type::{
+        flag_from_name, ParticleColor, VariantProperty, VariantType, CUSTOM, FLAG_IMMUTABLE, WALL,
+    },
+    world::World,
+};
+
+// a runaway script gets cut off instead of hanging the tick
+pub const MAX_OPERATIONS: u64 = 50_000;
+// only the last few distinct errors are kept
+const MAX_ERRORS: usize = 16;
+
+// scripts see the same 5x5 window the API can reach
+const REACH: i32 = 2;
+const SIDE: usize = (2 * REACH + 1) as usize;
+
+fn window_idx(dx: INT, dy: INT) -> Option<usize> {
+    let reach = REACH as INT;
+    if !(-reach..=reach).contains(&dx) || !(-reach..=reach).contains(&dy) {
+        return None;
+    }
+    Some(((dy + reach) as usize) * SIDE + (dx + reach) as usize)
+}
+
+// a particle as scripts see it, `element` is the short code ("WATR" or a custom one).
+// its fields are read and written with `me["name"]`
+#[derive(Clone)]
+pub struct ScriptParticle {
+    particle: Particle,
+    element: String,
+    fields: Vec<(String, Value)>,
+}
+
+impl ScriptParticle {
+    fn get_field(&mut self, name: &str) -> Dynamic {
+        match self.fields.iter().find(|(n, _)| n == name) {
+            Some((_, Value::F32(v))) => Dynamic::from(*v as FLOAT),
+            Some((_, value)) => Dynamic::from(value.to_f32() as INT),
+            None => Dynamic::UNIT,
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: Dynamic) -> Result<(), Box<EvalAltResult>> {
+        let Some((_, field)) = self.fields.iter_mut().find(|(n, _)| n == name) else {
+            return Err(format!("{} has no field {:?}", self.element, name).into());
+        };
+        let Some(v) = as_float(value) else {
+            return Err(format!("field {:?} takes a number", name).into());
+        };
+        *field = Value::from_f32(field.kind(), v);
+        Ok(())
+    }
+}
+
+// copy of the neighbourhood, writes are applied to the world once the script returns
+struct Window {
+    cells: [Particle; SIDE * SIDE],
+    fields: Vec<Vec<(String, Value)>>,
+    inside: [bool; SIDE * SIDE],
+    temperature: [f32; SIDE * SIDE],
+    pressure: [f32; SIDE * SIDE],
+    written: [bool; SIDE * SIDE],
+    env_written: [bool; SIDE * SIDE],
+}
+
+// the sandboxed API handed to scripts
+#[derive(Clone)]
+pub struct ScriptApi {
+    window: Arc<Mutex<Window>>,
+    elements: Elements,
+}
+
+impl ScriptApi {
+    fn capture(api: &mut API) -> ScriptApi {
+        let mut window = Window {
+            cells: [Particle::default(); SIDE * SIDE],
+            fields: vec![vec![]; SIDE * SIDE],
+            inside: [false; SIDE * SIDE],
+            temperature: [0.; SIDE * SIDE],
+            pressure: [0.; SIDE * SIDE],
+            written: [false; SIDE * SIDE],
+            env_written: [false; SIDE * SIDE],
+        };
+        for dy in -REACH..=REACH {
+            for dx in -REACH..=REACH {
+                let i = window_idx(dx as INT, dy as INT).unwrap();
+                let (x, y) = (api.x + dx, api.y + dy);
+                window.cells[i] = api.get(dx, dy);
+                window.fields[i] = api.world.fields(&window.cells[i]);
+                window.inside[i] = x >= 0 && x < api.world.width && y >= 0 && y < api.world.height;
+                if window.inside[i] {
+                    window.temperature[i] = api.get_temperature(x, y);
+                    window.pressure[i] = api.get_pressure(x, y);
+                }
+            }
+        }
+        ScriptApi {
+            window: Arc::new(Mutex::new(window)),
+            elements: api.world.elements.clone(),
+        }
+    }
+
+    fn apply(&self, api: &mut API) {
+        let window = self.window.lock().unwrap();
+        for dy in -REACH..=REACH {
+            for dx in -REACH..=REACH {
+                let i = window_idx(dx as INT, dy as INT).unwrap();
+                if window.written[i] {
+                    let mut particle = window.cells[i];
+                    for (name, value) in &window.fields[i] {
+                        // fields of another element are dropped when a particle changes
+                        let _ = api.set_field(&mut particle, name, *value);
+                    }
+                    api.set(dx, dy, particle);
+                }
+                if window.env_written[i] {
+                    let (x, y) = (api.x + dx, api.y + dy);
+                    api.set_temperature(x, y, window.temperature[i]);
+                    api.set_pressure(x, y, window.pressure[i]);
+                }
+            }
+        }
+    }
+
+    fn wrap(&self, particle: Particle, fields: Vec<(String, Value)>) -> ScriptParticle {
+        ScriptParticle {
+            element: self.elements.code_of(&particle),
+            particle,
+            fields,
+        }
+    }
+
+    // a particle that isn't in the world yet, its fields at their defaults
+    fn wrap_new(&self, particle: Particle) -> ScriptParticle {
+        let fields = metadata::schema(&self.elements, &particle.variant_type)
+            .map(|f| (f.name.to_string(), f.default))
+            .collect();
+        self.wrap(particle, fields)
+    }
+
+    fn get(&mut self, dx: INT, dy: INT) -> ScriptParticle {
+        // out of reach reads as wall, same as the edge of the world
+        let Some(i) = window_idx(dx, dy) else {
+            return self.wrap_new(Particle::new(WALL, 0, 0));
+        };
+        let window = self.window.lock().unwrap();
+        self.wrap(window.cells[i], window.fields[i].clone())
+    }
+
+    fn set(&mut self, dx: INT, dy: INT, particle: ScriptParticle) {
+        if let Some(i) = window_idx(dx, dy) {
+            let mut window = self.window.lock().unwrap();
+            window.cells[i] = particle.particle;
+            window.fields[i] = particle.fields;
+            window.written[i] = true;
+        }
+    }
+
+    // swap this cell with a neighbour, walls and the world edge don't move
+    fn swap(&mut self, dx: INT, dy: INT) -> bool {
+        let (Some(i), Some(center)) = (window_idx(dx, dy), window_idx(0, 0)) else {
+            return false;
+        };
+        let mut window = self.window.lock().unwrap();
+        let immutable = |p: &Particle| p.variant_type.has_flag(FLAG_IMMUTABLE);
+        if !window.inside[i] || immutable(&window.cells[i]) || immutable(&window.cells[center]) {
+            return false;
+        }
+        window.cells.swap(i, center);
+        window.fields.swap(i, center);
+        window.written[i] = true;
+        window.written[center] = true;
+        true
+    }
+
+    fn rand_int(&mut self, n: INT) -> INT {
+        rng::rand_int(n.clamp(0, i32::MAX as INT) as i32) as INT
+    }
+
+    fn once_in(&mut self, n: INT) -> bool {
+        self.rand_int(n) == 0
+    }
+
+    fn get_temperature(&mut self, dx: INT, dy: INT) -> FLOAT {
+        window_idx(dx, dy).map_or(0., |i| self.window.lock().unwrap().temperature[i] as FLOAT)
+    }
+
+    fn set_temperature(&mut self, dx: INT, dy: INT, temperature: FLOAT) {
+        if let Some(i) = window_idx(dx, dy) {
+            let mut window = self.window.lock().unwrap();
+            if window.inside[i] {
+                window.temperature[i] = temperature as f32;
+                window.env_written[i] = true;
+            }
+        }
+    }
+
+    fn get_pressure(&mut self, dx: INT, dy: INT) -> FLOAT {
+        window_idx(dx, dy).map_or(0., |i| self.window.lock().unwrap().pressure[i] as FLOAT)
+    }
+
+    fn set_pressure(&mut self, dx: INT, dy: INT, pressure: FLOAT) {
+        if let Some(i) = window_idx(dx, dy) {
+            let mut window = self.window.lock().unwrap();
+            if window.inside[i] {
+                window.pressure[i] = pressure as f32;
+                window.env_written[i] = true;
+            }
+        }
+    }
+
+    // a fresh particle of any element by code or name, `()` if there's no such element
+    fn element(&mut self, name: &str) -> Dynamic {
+        match self.elements.lookup(name) {
+            Some(variant_type) => Dynamic::from(self.wrap_new(Particle::new(variant_type, 0, 0))),
+            None => Dynamic::UNIT,
+        }
+    }
+}
+
+fn register_api(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ScriptApi>("Api")
+        .register_fn("get", ScriptApi::get)
+        .register_fn("set", ScriptApi::set)
+        .register_fn("swap", ScriptApi::swap)
+        .register_fn("rand_int", ScriptApi::rand_int)
+        .register_fn("once_in", ScriptApi::once_in)
+        .register_fn("get_temperature", ScriptApi::get_temperature)
+        .register_fn("set_temperature", ScriptApi::set_temperature)
+        .register_fn("get_pressure", ScriptApi::get_pressure)
+        .register_fn("set_pressure", ScriptApi::set_pressure)
+        .register_fn("element", |api: &mut ScriptApi, name: &str| {
+            api.element(name)
+        });
+
+    engine
+        .register_type_with_name::<ScriptParticle>("Particle")
+        .register_get("element", |p: &mut ScriptParticle| p.element.clone())
+        .register_get("weight", |p: &mut ScriptParticle| {
+            p.particle.variant_type.weight as INT
+        })
+        .register_get("property", |p: &mut ScriptParticle| {
+            format!("{:?}", p.particle.variant_type.variant_property)
+        })
+        .register_get_set(
+            "temperature",
+            |p: &mut ScriptParticle| p.particle.temperature as FLOAT,
+            |p: &mut ScriptParticle, t: FLOAT| p.particle.temperature = t as f32,
+        )
+        .register_get_set(
+            "strength",
+            |p: &mut ScriptParticle| p.particle.strength as INT,
+            |p: &mut ScriptParticle, s: INT| p.particle.strength = s.clamp(0, 255) as u8,
+        )
+        .register_get("ra", |p: &mut ScriptParticle| p.particle.ra as INT)
+        .register_get("rb", |p: &mut ScriptParticle| p.particle.rb as INT)
+        .register_fn("is_empty", |p: &mut ScriptParticle| {
+            p.particle.get_variant().is_empty()
+        })
+        .register_indexer_get(ScriptParticle::get_field)
+        .register_indexer_set(ScriptParticle::set_field);
+}
+
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    // no `import`, no eval, bounded work per call
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(1024);
+    engine.set_max_array_size(1024);
+    engine.set_max_map_size(256);
+    register_api(&mut engine);
+    engine
+}
+
+// errors from update functions, so a broken script doesn't fail silently
+#[derive(Clone, Default)]
+pub struct ScriptErrors(Arc<Mutex<Vec<String>>>);
+
+impl ScriptErrors {
+    fn push(&self, error: String) {
+        let mut errors = self.0.lock().unwrap();
+        if !errors.contains(&error) {
+            if errors.len() == MAX_ERRORS {
+                errors.remove(0);
+            }
+            errors.push(error);
+        }
+    }
+
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+struct ScriptElement {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    function: String,
+    errors: ScriptErrors,
+}
+
+impl Behaviour for ScriptElement {
+    fn update(&self, particle: Particle, api: &mut API) -> bool {
+        let script_api = ScriptApi::capture(api);
+        let me = script_api.wrap(particle, api.world.fields(&particle));
+        let result = self.engine.call_fn::<Dynamic>(
+            &mut Scope::new(),
+            &self.ast,
+            &self.function,
+            (script_api.clone(), me),
+        );
+        match result {
+            Ok(changed) => {
+                script_api.apply(api);
+                changed.as_bool().unwrap_or(false)
+            }
+            Err(err) => {
+                self.errors.push(format!("{}: {}", self.function, err));
+                false
+            }
+        }
+    }
+}
+
+pub struct Script {
+    pub elements: Vec<VariantType>,
+    pub errors: ScriptErrors,
+}
+
+// a `define` call, checked but not yet registered
+struct ElementSpec {
+    name: String,
+    code: String,
+    variant_type: VariantType,
+    fields: Option<Vec<Field>>,
+    behaviour: Option<Arc<dyn Behaviour>>,
+    group: Option<String>,
+    description: String,
+}
+
+fn field<T>(
+    def: &Map,
+    key: &str,
+    parse: impl Fn(Dynamic) -> Option<T>,
+) -> Result<Option<T>, String> {
+    match def.get(key) {
+        None => Ok(None),
+        Some(value) => parse(value.clone())
+            .map(Some)
+            .ok_or_else(|| format!("element field {:?} has the wrong type", key)),
+    }
+}
+
+fn as_string(value: Dynamic) -> Option<String> {
+    value.into_string().ok()
+}
+
+fn as_float(value: Dynamic) -> Option<f32> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|i| i as FLOAT))
+        .map(|f| f as f32)
+}
+
+fn as_byte(value: Dynamic) -> Option<u8> {
+    value.as_int().ok().and_then(|i| u8::try_from(i).ok())
+}
+
+fn as_strings(value: Dynamic) -> Option<Vec<String>> {
+    value
+        .into_array()
+        .ok()?
+        .into_iter()
+        .map(as_string)
+        .collect()
+}
+
+// [r, g, b] or [r, g, b, a]
+fn as_color(value: Dynamic) -> Option<ParticleColor> {
+    let rgba: Vec<u8> = value
+        .into_array()
+        .ok()?
+        .into_iter()
+        .map(as_byte)
+        .collect::<Option<_>>()?;
+    match rgba[..] {
+        [r, g, b] => Some(ParticleColor::from_rgba((r, g, b, 255))),
+        [r, g, b, a] => Some(ParticleColor::from_rgba((r, g, b, a))),
+        _ => None,
+    }
+}
+
+// `fields: #{ charge: "u8", growth: "f32" }`, all starting at zero
+fn element_fields(def: &Map) -> Result<Option<Vec<Field>>, String> {
+    let Some(fields) = field(def, "fields", |v| v.try_cast::<Map>())? else {
+        return Ok(None);
+    };
+    let fields = fields
+        .into_iter()
+        .map(|(name, kind)| {
+            let kind = as_string(kind).ok_or_else(|| format!("field {:?} needs a kind", name))?;
+            Ok(Field::named(&name, FieldKind::from_name(&kind)?))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    metadata::check_fields(&fields)?;
+    Ok(Some(fields))
+}
+
+fn element_type(def: &Map) -> Result<VariantType, String> {
+    let mut variant_type = CUSTOM;
+    if let Some(color) = field(def, "color", as_color)? {
+        variant_type.color = color;
+    }
+    if let Some(weight) = field(def, "weight", as_byte)? {
+        variant_type.weight = weight;
+    }
+    if let Some(strength) = field(def, "strength", as_byte)? {
+        variant_type.strength = strength;
+    }
+    if let Some(temperature) = field(def, "temperature", as_float)? {
+        variant_type.base_temperature = temperature;
+    }
+    if let Some(property) = field(def, "property", as_string)? {
+        variant_type.variant_property = VariantProperty::from_name(&property)?;
+    }
+    for flag in field(def, "flags", as_strings)?.unwrap_or_default() {
+        variant_type.flags |= flag_from_name(&flag)?;
+    }
+    Ok(variant_type)
+}
+
+// run a script's top level, which declares elements with `define(#{ .. })`:
+//
+//     define(#{ name: "Goo", code: "GOO", color: [90, 200, 60], weight: 40,
+//               property: "liquid", flags: ["burns"], group: "Custom", update: "goo",
+//               fields: #{ charge: "u8" }, description: "Spreads through water" });
+//     fn goo(api, me) { ... me["charge"] += 1; ... true if it changed anything }
+//
+// the elements are registered into the world, and into `groups` under their group
+pub fn load_script(
+    world: &mut World,
+    groups: &mut ElementManager,
+    source: &str,
+) -> Result<Script, String> {
+    let mut engine = sandboxed_engine();
+    let defs = Arc::new(Mutex::new(Vec::<Map>::new()));
+    let sink = defs.clone();
+    engine.register_fn("define", move |def: Map| sink.lock().unwrap().push(def));
+
+    let ast = engine.compile(source).map_err(|e| e.to_string())?;
+    engine.run_ast(&ast).map_err(|e| e.to_string())?;
+
+    let engine = Arc::new(engine);
+    let ast = Arc::new(ast);
+    let errors = ScriptErrors::default();
+    let defs = std::mem::take(&mut *defs.lock().unwrap());
+
+    // every definition is checked before the world is touched, so a bad one changes nothing
+    let mut specs = vec![];
+    for def in defs {
+        let name = field(&def, "name", as_string)?.ok_or("element is missing a name")?;
+        let code = field(&def, "code", as_string)?.unwrap_or_else(|| name.to_uppercase());
+        let variant_type = element_type(&def).map_err(|e| format!("{}: {}", name, e))?;
+        let fields = element_fields(&def).map_err(|e| format!("{}: {}", name, e))?;
+        let group = field(&def, "group", as_string)?;
+        let description = field(&def, "description", as_string)?.unwrap_or_default();
+
+        let behaviour: Option<Arc<dyn Behaviour>> = match field(&def, "update", as_string)? {
+            Some(function) => {
+                if !ast
+                    .iter_functions()
+                    .any(|f| f.name == function && f.params.len() == 2)
+                {
+                    return Err(format!("{}: no function {}(api, me)", name, function));
+                }
+                Some(Arc::new(ScriptElement {
+                    engine: engine.clone(),
+                    ast: ast.clone(),
+                    function,
+                    errors: errors.clone(),
+                }))
+            }
+            None => None,
+        };
+        specs.push(ElementSpec {
+            name,
+            code,
+            variant_type,
+            fields,
+            behaviour,
+            group,
+            description,
+        });
+    }
+
+    let mut elements = vec![];
+    for spec in specs {
+        let variant_type =
+            world.register_element(&spec.name, &spec.code, spec.variant_type, spec.behaviour);
+        if let Some(fields) = spec.fields {
+            // already checked, this can't fail
+            world.register_fields(variant_type, fields)?;
+        }
+        if let Some(group) = spec.group {
+            let mut info = ElementInfo::new(&spec.name, &spec.code, variant_type);
+            info.description = spec.description;
+            groups.add_to_group(&group, info);
+        }
+        elements.push(variant_type);
+    }
+
+    Ok(Script { elements, errors })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Variant;
+
+    const SCRIPT: &str = r#"
+        define(#{ name: "Goo", code: "GOO", color: [90, 200, 60], weight: 40,
+                  property: "solid", group: "Slime", update: "goo",
+                  fields: #{ eaten: "u16" }, description: "Turns water into goo" });
+        define(#{ name: "Rock", property: "powder", weight: 120, group: "Slime" });
+
+        // goo turns water next to it into more goo and warms up the air
+        fn goo(api, me) {
+            let below = api.get(0, 1);
+            if below.element == "WATR" {
+                me["eaten"] += 1;
+                api.set(0, 1, me);
+                api.set_temperature(0, 0, api.get_temperature(0, 0) + 10.0);
+                return true;
+            }
+            false
+        }
+    "#;
+
+    #[test]
+    fn test_script_element() {
+        let mut world = World::new(3, 4);
+        let mut groups = ElementManager::new();
+        let script = load_script(&mut world, &mut groups, SCRIPT).unwrap();
+        assert_eq!(script.elements.len(), 2);
+        assert_eq!(groups.get_group("Slime"), script.elements);
+        assert_eq!(
+            groups.find("goo").unwrap().description,
+            "Turns water into goo"
+        );
+
+        let goo = world.find_element("goo").unwrap();
+        for y in 1..4 {
+            world.set_particle(1, y, Variant::Water);
+            world.set_particle(0, y, Variant::Wall);
+            world.set_particle(2, y, Variant::Wall);
+        }
+        world.set_element(1, 0, goo);
+        for _ in 0..10 {
+            world.tick();
+        }
+        assert!(script.errors.take().is_empty());
+        assert_eq!(world.get_particle(1, 3).variant_type, goo);
+        assert!(world.get_temperature(1, 0) > 22.);
+        // every copy counts the water eaten before it
+        assert_eq!(world.get_u16(&world.get_particle(1, 3), "eaten"), 3);
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let mut world = World::new(3, 3);
+        let mut groups = ElementManager::new();
+        assert!(load_script(
+            &mut world,
+            &mut groups,
+            "define(#{ name: \"X\", update: \"nope\" });"
+        )
+        .is_err());
+        assert!(load_script(
+            &mut world,
+            &mut groups,
+            "define(#{ name: \"X\", property: \"plasma\" });"
+        )
+        .is_err());
+        assert!(load_script(
+            &mut world,
+            &mut groups,
+            "define(#{ name: \"X\", fields: #{ spark: \"u8\" } });"
+        )
+        .is_err());
+
+        // a mistake in a later definition leaves the earlier ones out too
+        let source = "define(#{ name: \"Y\" }); define(#{ name: \"Z\", group: 3 });";
+        assert!(load_script(&mut world, &mut groups, source).is_err());
+        assert!(world.find_element("Y").is_none());
+
+        // runaway scripts are cut off and reported
+        let source = "define(#{ name: \"Spin\", update: \"spin\" }); fn spin(api, me) { loop {} }";
+        let script = load_script(&mut world, &mut groups, source).unwrap();
+        world.set_element(1, 1, script.elements[0]);
+        world.tick();
+        assert_eq!(script.errors.take().len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use std::fs;
+
+use crate::{
+    colors::GRID_COLOR,
+    render::{upscale, Rect, Renderer, View},
+    variant_type::ParticleColor,
+    world::World,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    // a line every this many cells
+    pub every: u32,
+    // blended over the image by its alpha
+    pub color: ParticleColor,
+}
+
+// how to take a still of the world, the default matches `World::save`
+#[derive(Clone, Copy, Debug)]
+pub struct Snapshot {
+    // each cell becomes a scale x scale block
+    pub scale: u32,
+    // keep the alpha channel, otherwise it is dropped and the colours kept as they are,
+    // which is what `World::load` reads back
+    pub alpha: bool,
+    // only this part of the world, clipped to it
+    pub region: Option<Rect>,
+    pub grid: Option<Grid>,
+    pub view: View,
+    pub glow: bool,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Snapshot {
+            scale: 1,
+            alpha: false,
+            region: None,
+            grid: None,
+            view: View::Normal,
+            glow: false,
+        }
+    }
+}
+
+impl Grid {
+    pub fn new(every: u32) -> Grid {
+        Grid {
+            every,
+            color: GRID_COLOR,
+        }
+    }
+}
+
+// encode a snapshot as a png in memory
+pub fn snapshot_png(world: &World, snapshot: &Snapshot) -> Result<Vec<u8>, String> {
+    let whole = Rect {
+        x: 0,
+        y: 0,
+        width: world.width,
+        height: world.height,
+    };
+    let region = match snapshot.region {
+        Some(region) => clip(region, whole).ok_or("the region is outside the world")?,
+        None => whole,
+    };
+
+    let mut renderer = Renderer::new(world.width, world.height);
+    renderer.set_view(snapshot.view);
+    renderer.set_glow(snapshot.glow);
+    renderer.update(world);
+    let mut cells = vec![];
+    renderer.copy_rect(region, &mut cells);
+
+    let scale = snapshot.scale.max(1);
+    let (width, height) = (region.width as u32 * scale, region.height as u32 * scale);
+    let mut image = vec![];
+    upscale(&cells, region.width as usize, scale as usize, &mut image);
+    if let Some(grid) = snapshot.grid {
+        draw_grid(
+            &mut image,
+            width,
+            height,
+            scale * grid.every.max(1),
+            grid.color,
+        );
+    }
+    if !snapshot.alpha {
+        image = image
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
+    }
+
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(match snapshot.alpha {
+        true => png::ColorType::Rgba,
+        false => png::ColorType::Rgb,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&image).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+pub fn save_png(world: &World, snapshot: &Snapshot, path: &str) -> Result<(), String> {
+    let png = snapshot_png(world, snapshot)?;
+    fs::write(path, png).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn clip(rect: Rect, bounds: Rect) -> Option<Rect> {
+    let x = rect.x.max(bounds.x);
+    let y = rect.y.max(bounds.y);
+    let right = (rect.x + rect.width).min(bounds.x + bounds.width);
+    let bottom = (rect.y + rect.height).min(bounds.y + bounds.height);
+    (right > x && bottom > y).then_some(Rect {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    })
+}
+
+// one pixel lines along the top and left of every `spacing` pixels
+fn draw_grid(image: &mut [u8], width: u32, height: u32, spacing: u32, color: ParticleColor) {
+    let factor = color.a as u32;
+    for y in 0..height {
+        for x in 0..width {
+            if x % spacing != 0 && y % spacing != 0 {
+                continue;
+            }
+            let idx = ((x + y * width) * 4) as usize;
+            let pixel = &mut image[idx..idx + 4];
+            for (channel, line) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+                *channel = ((*channel as u32 * (255 - factor) + line as u32 * factor) / 255) as u8;
+            }
+            pixel[3] = pixel[3].max(color.a);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{particle::particle_to_color, variant::Variant};
+
+    fn decode(png: &[u8]) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        (info, image)
+    }
+
+    #[test]
+    fn test_default_matches_save() {
+        let mut world = World::new(4, 3);
+        world.set_particle(1, 1, Variant::Wall);
+        // translucent, its colour is kept rather than darkened by its alpha
+        world.set_particle(2, 1, Variant::Smoke);
+        let (info, image) = decode(&snapshot_png(&world, &Snapshot::default()).unwrap());
+        assert_eq!((info.width, info.height), (4, 3));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(image[(1 + 4) * 3..(1 + 4) * 3 + 3], [128, 128, 128]);
+        let smoke = particle_to_color(&world, world.get_particle(2, 1)).to_rgb8();
+        assert_eq!(
+            image[(2 + 4) * 3..(2 + 4) * 3 + 3],
+            [smoke.0, smoke.1, smoke.2]
+        );
+        assert_eq!(image[0..3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_scaled_cropped_with_grid() {
+        let mut world = World::new(10, 10);
+        world.set_particle(5, 5, Variant::Wall);
+        let snapshot = Snapshot {
+            scale: 4,
+            alpha: true,
+            region: Some(Rect {
+                x: 4,
+                y: 4,
+                width: 3,
+                height: 20,
+            }),
+            grid: Some(Grid::new(1)),
+            ..Default::default()
+        };
+        let (info, image) = decode(&snapshot_png(&world, &snapshot).unwrap());
+        // clipped to 3x6 cells of 4x4 pixels
+        assert_eq!((info.width, info.height), (12, 24));
+        let pixel = |x: usize, y: usize| image[(x + y * 12) * 4..(x + y * 12) * 4 + 4].to_vec();
+        // the wall is cell (1, 1) of the crop, inside its block there's no grid line
+        assert_eq!(pixel(6, 6), vec![128, 128, 128, 255]);
+        // empty cells stay transparent apart from the grid
+        assert_eq!(pixel(1, 1)[3], 0);
+        assert_eq!(pixel(0, 1)[3], GRID_COLOR.a);
+
+        let outside = Snapshot {
+            region: Some(Rect {
+                x: 20,
+                y: 0,
+                width: 4,
+                height: 4,
+            }),
+            ..Default::default()
+        };
+        assert!(snapshot_png(&world, &outside).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{
+    api::API,
+    events::Event,
+    metadata::{Value, NO_STATE},
+    particle::Particle,
+    variant::{Variant, EMPTY_CELL},
+    variant_type::{VariantType, SALT, SALT_WATER, WATER, WTVP},
+    world::World,
+};
+
+// concentration is measured in salt grains dissolved per water cell
+pub const SALT_GRAIN: f32 = 0.25;
+pub const SATURATION: f32 = 1.0;
+// what a freshly placed SaltWater cell holds
+pub const BRINE: f32 = 0.5;
+pub const DIFFUSION: f32 = 0.25;
+pub const BOILING_POINT: f32 = 100.;
+// every grain per cell raises the boiling point a little
+pub const BOILING_ELEVATION: f32 = 4.;
+
+const NBRS: [(i32, i32); 8] = [
+    (0, -1),
+    (-1, 0),
+    (1, 0),
+    (0, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+// salt only dissolves in water, other liquids neither take it up nor pass it along
+pub fn is_solvent(particle: &Particle) -> bool {
+    matches!(particle.get_variant(), Variant::Water | Variant::SaltWater)
+}
+
+// kept in the "concentration" field of water, brine and steam
+pub fn concentration(world: &World, particle: &Particle) -> f32 {
+    world.get_f32(particle, "concentration")
+}
+
+// water with anything dissolved in it is salt water
+fn solvent_type(concentration: f32) -> VariantType {
+    if concentration > f32::EPSILON {
+        SALT_WATER
+    } else {
+        WATER
+    }
+}
+
+pub(crate) fn with_concentration(
+    world: &mut World,
+    particle: Particle,
+    concentration: f32,
+) -> Particle {
+    let concentration = concentration.max(0.);
+    let mut particle = Particle {
+        variant_type: solvent_type(concentration),
+        ..particle
+    };
+    world
+        .set_field(&mut particle, "concentration", Value::F32(concentration))
+        .unwrap();
+    particle
+}
+
+// the solvent stays where it is, only the steam carries `concentration` away
+fn steam(world: &mut World, particle: Particle, concentration: f32) -> Particle {
+    let mut steam = Particle {
+        variant_type: WTVP,
+        ..particle
+    };
+    world
+        .set_field(&mut steam, "concentration", Value::F32(concentration))
+        .unwrap();
+    steam
+}
+
+// a salt grain next to unsaturated water dissolves into it
+pub fn dissolve(particle: Particle, api: &mut API) -> bool {
+    if api.get(0, 0).get_variant() != particle.get_variant() {
+        return false;
+    }
+
+    for (dx, dy) in NBRS {
+        let nbr = api.get(dx, dy);
+        let held = concentration(api.world, &nbr);
+        if is_solvent(&nbr) && held + SALT_GRAIN <= SATURATION {
+            let brine = with_concentration(api.world, nbr, held + SALT_GRAIN);
+            api.set(dx, dy, brine);
+            api.set(0, 0, EMPTY_CELL);
+            api.emit(Event::Reaction {
+                x: api.x + dx,
+                y: api.y + dy,
+                reactants: (Variant::Salt, nbr.get_variant()),
+                product: Variant::SaltWater,
+            });
+            return true;
+        }
+    }
+    false
+}
+
+// boiling, precipitation and diffusion for water and salt water
+pub fn update(particle: Particle, api: &mut API) -> bool {
+    // the cell flowed away before its update ran
+    if api.get(0, 0).get_variant() != particle.get_variant() {
+        return false;
+    }
+
+    let held = concentration(api.world, &particle);
+    let boiling_point = BOILING_POINT + BOILING_ELEVATION * held;
+    if particle.temperature > boiling_point {
+        return boil(particle, held, api);
+    }
+
+    if held >= SATURATION + SALT_GRAIN && precipitate(particle, held, api) {
+        return true;
+    }
+
+    // even out with a random neighbouring water cell
+    let (dx, dy) = api.rand_vec();
+    let nbr = api.get(dx, dy);
+    if (dx, dy) == (0, 0) || !is_solvent(&nbr) {
+        return false;
+    }
+
+    let nbr_held = concentration(api.world, &nbr);
+    let flux = (held - nbr_held) * DIFFUSION;
+    if flux.abs() <= f32::EPSILON {
+        return false;
+    }
+    let particle = with_concentration(api.world, particle, held - flux);
+    let nbr = with_concentration(api.world, nbr, nbr_held + flux);
+    api.set(0, 0, particle);
+    api.set(dx, dy, nbr);
+    true
+}
+
+// drop one grain out of a supersaturated cell into free space
+fn precipitate(particle: Particle, held: f32, api: &mut API) -> bool {
+    for (dx, dy) in NBRS.iter().rev() {
+        if api.get(*dx, *dy).get_variant() == Variant::Empty {
+            api.emit(Event::PhaseChange {
+                x: api.x + dx,
+                y: api.y + dy,
+                from: particle.get_variant(),
+                to: Variant::Salt,
+            });
+            api.set(*dx, *dy, Particle::new(SALT, 0, 0));
+            let particle = with_concentration(api.world, particle, held - SALT_GRAIN);
+            api.set(0, 0, particle);
+            return true;
+        }
+    }
+    false
+}
+
+// the water turns to steam. its salt goes into the surrounding brine, or falls out as
+// grains when this was the last of the water, with what's short of a grain carried off
+// in the steam. without room for every grain and the steam it doesn't boil yet
+fn boil(particle: Particle, held: f32, api: &mut API) -> bool {
+    let mut cells = vec![(0, 0)];
+    let mut grains = 0;
+    let mut carried = 0.;
+
+    let brine = NBRS
+        .into_iter()
+        .find(|(dx, dy)| is_solvent(&api.get(*dx, *dy)));
+    match brine {
+        _ if held <= 0. => (),
+        Some((dx, dy)) => {
+            let nbr = api.get(dx, dy);
+            let nbr_held = concentration(api.world, &nbr);
+            let nbr = with_concentration(api.world, nbr, nbr_held + held);
+            api.set(dx, dy, nbr);
+        }
+        None => {
+            grains = (held / SALT_GRAIN).floor() as usize;
+            carried = held - grains as f32 * SALT_GRAIN;
+            cells.extend(
+                NBRS.iter()
+                    .rev()
+                    .copied()
+                    .filter(|(dx, dy)| api.get(*dx, *dy).get_variant() == Variant::Empty)
+                    .take(grains),
+            );
+            if cells.len() < grains + 1 {
+                return false;
+            }
+        }
+    }
+
+    api.emit(Event::PhaseChange {
+        x: api.x,
+        y: api.y,
+        from: particle.get_variant(),
+        to: Variant::WTVP,
+    });
+    // grains fill this cell first, then the free cells around it, the steam gets the last
+    let salt = Particle {
+        variant_type: SALT,
+        state: NO_STATE,
+        ..particle
+    };
+    let steam = steam(api.world, particle, carried.max(0.));
+    for (i, (dx, dy)) in cells.into_iter().enumerate() {
+        match i < grains {
+            true => api.set(dx, dy, salt),
+            false => api.set(dx, dy, steam),
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    fn count(world: &World, variant: Variant) -> usize {
+        world
+            .particles
+            .iter()
+            .filter(|p| p.get_variant() == variant)
+            .count()
+    }
+
+    fn dissolved(world: &World) -> f32 {
+        world
+            .particles
+            .iter()
+            .map(|p| concentration(world, p))
+            .sum()
+    }
+
+    #[test]
+    fn test_salt_dissolves_up_to_saturation() {
+        // one water cell in a pile of salt can only take SATURATION worth
+        let mut world = World::new(1, 8);
+        for y in 0..7 {
+            world.set_particle(0, y, Variant::Salt);
+        }
+        world.set_particle(0, 7, Variant::Water);
+        for _ in 0..50 {
+            world.tick();
+        }
+        let water = *world.particles.iter().find(|p| is_solvent(p)).unwrap();
+        assert_eq!(water.get_variant(), Variant::SaltWater);
+        assert_eq!(concentration(&world, &water), SATURATION);
+        assert_eq!(count(&world, Variant::Salt), 7 - 4);
+    }
+
+    #[test]
+    fn test_concentration_diffuses() {
+        let mut world = World::new(2, 1);
+        world.set_particle(0, 0, Variant::SaltWater);
+        world.set_particle(1, 0, Variant::Water);
+        for _ in 0..200 {
+            world.tick();
+        }
+        let (a, b) = (world.get_particle(0, 0), world.get_particle(1, 0));
+        assert!((concentration(&world, &a) - concentration(&world, &b)).abs() < 0.05);
+        assert!((dissolved(&world) - BRINE).abs() < 1e-4);
+        assert_eq!(count(&world, Variant::SaltWater), 2);
+    }
+
+    #[test]
+    fn test_boiling_leaves_salt() {
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 2, Variant::SaltWater);
+        let idx = world.get_idx(1, 2);
+        world.particles[idx].temperature = 150.;
+        for _ in 0..5 {
+            world.tick();
+        }
+
+        // BRINE is two grains worth
+        assert_eq!(count(&world, Variant::Salt), 2);
+        assert_eq!(count(&world, Variant::SaltWater), 0);
+    }
+
+    #[test]
+    fn test_boiling_waits_for_room() {
+        // nowhere for the two grains and the steam to go
+        let mut world = World::new(1, 1);
+        world.set_particle(0, 0, Variant::SaltWater);
+        world.add_heat(0, 0, 500.);
+        world.tick();
+        assert_eq!(world.get_particle(0, 0).get_variant(), Variant::SaltWater);
+        assert_eq!(dissolved(&world), BRINE);
+
+        // a grain and a half, the half leaves with the steam
+        let mut world = World::new(3, 1);
+        world.set_particle(1, 0, Variant::SaltWater);
+        let idx = world.get_idx(1, 0);
+        let brine = world.particles[idx];
+        world.particles[idx] = with_concentration(&mut world, brine, 1.5 * SALT_GRAIN);
+        world.add_heat(1, 0, 500.);
+        for _ in 0..5 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Salt), 1);
+        assert_eq!(count(&world, Variant::WTVP), 1);
+        assert_eq!(dissolved(&world), 0.5 * SALT_GRAIN);
+    }
+
+    #[test]
+    fn test_boiling_concentrates_brine() {
+        let mut world = World::new(1, 3);
+        world.set_particle(0, 1, Variant::SaltWater);
+        world.set_particle(0, 2, Variant::SaltWater);
+        let idx = world.get_idx(0, 1);
+        world.particles[idx].temperature = 150.;
+        world.tick();
+
+        assert_eq!(count(&world, Variant::SaltWater), 1);
+        assert_eq!(concentration(&world, &world.get_particle(0, 2)), 2. * BRINE);
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+};
+
+use crate::{
+    variant::Variant,
+    variant_type::{VariantProperty, VARIANT_COUNT},
+    world::World,
+};
+
+// columns every sample has, element codes follow these in the csv
+pub const COLUMNS: [&str; 10] = [
+    "tick",
+    "particles",
+    "active",
+    "sleeping",
+    "total_heat",
+    "average_temperature",
+    "mass_solid",
+    "mass_powder",
+    "mass_liquid",
+    "mass_gas",
+];
+
+// the world at one tick. empty cells aren't counted anywhere
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sample {
+    pub tick: u64,
+    // by short code, runtime elements included
+    pub counts: BTreeMap<String, u32>,
+    // sum of particle temperatures
+    pub total_heat: f32,
+    pub average_temperature: f32,
+    // summed weights, indexed by `VariantProperty::to_u8`
+    pub mass: [u32; 4],
+    // cells that changed since the previous sample, and the rest
+    pub active: u32,
+    pub sleeping: u32,
+}
+
+impl Sample {
+    // `previous` holds what each cell held at the last sample, and is updated to now
+    fn of(world: &World, tick: u64, previous: &mut Vec<u32>) -> Sample {
+        let mut builtin = [0u32; VARIANT_COUNT];
+        let mut custom: BTreeMap<u16, u32> = BTreeMap::new();
+        let mut sample = Sample {
+            tick,
+            ..Default::default()
+        };
+        previous.resize(world.particles.len(), u32::MAX);
+
+        for (idx, particle) in world.particles.iter().enumerate() {
+            let variant_type = particle.variant_type;
+            let key = (variant_type.custom as u32) << 8 | variant_type.source_variant as u32;
+            let moved = std::mem::replace(&mut previous[idx], key) != key;
+            if key == Variant::Empty as u32 {
+                continue;
+            }
+            match variant_type.custom {
+                0 => builtin[variant_type.source_variant as usize] += 1,
+                id => *custom.entry(id).or_default() += 1,
+            }
+            sample.total_heat += particle.temperature;
+            sample.mass[variant_type.variant_property.to_u8() as usize] +=
+                variant_type.weight as u32;
+            // everything rewrites itself each tick, so active means it arrived, reacted or
+            // its element reported a change
+            match moved || world.modified_indices.contains(&idx) {
+                true => sample.active += 1,
+                false => sample.sleeping += 1,
+            }
+        }
+
+        for (variant, count) in builtin.iter().enumerate() {
+            if *count > 0 {
+                let code = Variant::from_u8(variant as u8).get_name();
+                sample.counts.insert(code.to_string(), *count);
+            }
+        }
+        for (id, count) in custom {
+            let code = match world.elements.get(id) {
+                Some(element) => element.code.clone(),
+                None => format!("#{}", id),
+            };
+            sample.counts.insert(code, count);
+        }
+
+        let particles = sample.particles();
+        if particles > 0 {
+            sample.average_temperature = sample.total_heat / particles as f32;
+        }
+        sample
+    }
+
+    pub fn particles(&self) -> u32 {
+        self.active + self.sleeping
+    }
+
+    pub fn count(&self, code: &str) -> u32 {
+        self.counts.get(code).copied().unwrap_or(0)
+    }
+
+    pub fn mass(&self, property: VariantProperty) -> u32 {
+        self.mass[property.to_u8() as usize]
+    }
+
+    // one of COLUMNS, or an element code
+    pub fn get(&self, column: &str) -> f32 {
+        match column {
+            "tick" => self.tick as f32,
+            "particles" => self.particles() as f32,
+            "active" => self.active as f32,
+            "sleeping" => self.sleeping as f32,
+            "total_heat" => self.total_heat,
+            "average_temperature" => self.average_temperature,
+            "mass_solid" => self.mass[0] as f32,
+            "mass_powder" => self.mass[1] as f32,
+            "mass_liquid" => self.mass[2] as f32,
+            "mass_gas" => self.mass[3] as f32,
+            code => self.count(code) as f32,
+        }
+    }
+}
+
+// a rolling window of samples, call `record` once a tick
+#[derive(Clone, Debug)]
+pub struct Stats {
+    // samples kept, the oldest are dropped first
+    pub capacity: usize,
+    // sample every this many ticks
+    pub every: u32,
+    samples: VecDeque<Sample>,
+    ticks: u64,
+    // element of each cell at the last sample
+    previous: Vec<u32>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            capacity: 600,
+            every: 1,
+            samples: VecDeque::new(),
+            ticks: 0,
+            previous: vec![],
+        }
+    }
+}
+
+impl Stats {
+    pub fn new(capacity: usize) -> Stats {
+        Stats {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    // returns the sample when one was taken this tick
+    pub fn record(&mut self, world: &World) -> Option<&Sample> {
+        let tick = self.ticks;
+        self.ticks += 1;
+        if !tick.is_multiple_of(self.every.max(1) as u64) {
+            return None;
+        }
+        while self.samples.len() >= self.capacity.max(1) {
+            self.samples.pop_front();
+        }
+        let sample = Sample::of(world, tick, &mut self.previous);
+        self.samples.push_back(sample);
+        self.samples.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.ticks = 0;
+        self.previous.clear();
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.back()
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.iter()
+    }
+
+    // a column over time, oldest first, for graphs
+    pub fn series(&self, column: &str) -> Vec<f32> {
+        self.samples.iter().map(|s| s.get(column)).collect()
+    }
+
+    // every element seen in the window, in code order
+    pub fn codes(&self) -> Vec<&str> {
+        let mut codes: Vec<&str> = self
+            .samples
+            .iter()
+            .flat_map(|s| s.counts.keys().map(String::as_str))
+            .collect();
+        codes.sort_unstable();
+        codes.dedup();
+        codes
+    }
+
+    // a header row of COLUMNS and element codes, then a row per sample
+    pub fn to_csv(&self) -> String {
+        let codes = self.codes();
+        let mut csv = COLUMNS.join(",");
+        for code in &codes {
+            csv.push(',');
+            csv.push_str(code);
+        }
+        csv.push('\n');
+
+        for sample in &self.samples {
+            let row: Vec<String> = COLUMNS
+                .iter()
+                .chain(codes.iter())
+                .map(|column| sample.get(column).to_string())
+                .collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn save_csv(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_csv()).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant_type::SAND;
+
+    #[test]
+    fn test_sample() {
+        let mut world = World::new(4, 4);
+        let mut stats = Stats::default();
+        world.set_particle(0, 0, Variant::Sand);
+        world.set_particle(1, 3, Variant::Sand);
+        world.set_particle(2, 3, Variant::Wall);
+        assert_eq!(stats.record(&world).unwrap().active, 3);
+        world.tick();
+
+        let sample = stats.record(&world).unwrap();
+        assert_eq!(sample.count("SAND"), 2);
+        assert_eq!(sample.count("WALL"), 1);
+        assert_eq!(sample.particles(), 3);
+        // only the falling grain moved
+        assert_eq!((sample.active, sample.sleeping), (1, 2));
+        assert_eq!(sample.mass(VariantProperty::Powder), 2 * SAND.weight as u32);
+        assert_eq!(sample.average_temperature, sample.total_heat / 3.);
+    }
+
+    #[test]
+    fn test_rolling_csv() {
+        let mut world = World::new(4, 4);
+        let mut stats = Stats {
+            capacity: 3,
+            every: 2,
+            ..Default::default()
+        };
+        for tick in 0..8 {
+            if tick == 4 {
+                world.set_particle(0, 0, Variant::Water);
+            }
+            stats.record(&world);
+            world.tick();
+        }
+
+        // ticks 0, 2, 4 and 6 were sampled, the first has been dropped
+        assert_eq!(stats.series("tick"), vec![2., 4., 6.]);
+        assert_eq!(stats.series("WATR"), vec![0., 1., 1.]);
+        let csv = stats.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next().unwrap(), format!("{},WATR", COLUMNS.join(",")));
+        assert!(lines.next().unwrap().starts_with("2,0,0,0,"));
+        assert_eq!(lines.count(), 2);
+    }
+}
@@ -1,7 +1,16 @@
 use std::fmt::Display;
 
 pub use crate::{api::API, particle::Particle, variant_type::VariantProperty};
-use crate::{particle::Velocity, variant_type::*};
+use crate::{
+    atmosphere::{self, Gas},
+    electricity, elements,
+    events::Event,
+    metadata::{Value, NO_STATE},
+    particle::Velocity,
+    solution,
+    variant_type::*,
+    world::World,
+};
 use serde::{Deserialize, Serialize};
 
 pub static EMPTY_CELL: Particle = Particle {
@@ -13,17 +22,7 @@ pub static EMPTY_CELL: Particle = Particle {
     temperature: 0.,
     ra: 0,
     rb: 0,
-};
-
-pub static EMPTY_LL: Particle = Particle {
-    variant_type: EMPTY_L,
-    clock: 0,
-    strength: 0,
-    modified: false,
-    velocity: Velocity { x: 0., y: 0. },
-    temperature: 0.,
-    ra: 0,
-    rb: 0,
+    state: NO_STATE,
 };
 
 #[repr(u8)]
@@ -54,7 +53,53 @@ pub enum Variant {
     WTVP = 15, //water vapor
 
     //LIFE
-    GOL = 16, //game of life
+    GOL = 16,  //game of life, B3/S23
+    HLIF = 18, //highlife, B36/S23
+    SEED = 19, //seeds, B2/S
+    DNGT = 20, //day & night, B3678/S34678
+
+    //ELECTRONICS
+    BTRY = 21, //power source
+    SWCH = 22, //switch, conducts when on
+    WIRE = 23, //wireworld logic wire
+
+    // ELEMENT PACK
+    Lava = 24,
+    Stone = 25,
+    Ice = 26,
+    Oil = 27,
+    Acid = 28,
+    Plant = 29,
+
+    // runtime defined, the element is variant_type.custom, see `elements`
+    Custom = 30,
+}
+
+// wireworld cell state, kept in the "state" metadata field of WIRE particles
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WireState {
+    Conductor = 0,
+    Head = 1,
+    Tail = 2,
+}
+
+impl WireState {
+    pub fn from_u8(n: u8) -> WireState {
+        match n {
+            1 => WireState::Head,
+            2 => WireState::Tail,
+            _ => WireState::Conductor,
+        }
+    }
+
+    pub fn of(world: &World, particle: &Particle) -> Option<WireState> {
+        if particle.get_variant() == Variant::WIRE {
+            Some(WireState::from_u8(world.get_u8(particle, "state")))
+        } else {
+            None
+        }
+    }
 }
 
 impl Display for Variant {
@@ -84,6 +129,19 @@ impl Variant {
             15 => Variant::WTVP,
             16 => Variant::GOL,
             17 => Variant::Glass,
+            18 => Variant::HLIF,
+            19 => Variant::SEED,
+            20 => Variant::DNGT,
+            21 => Variant::BTRY,
+            22 => Variant::SWCH,
+            23 => Variant::WIRE,
+            24 => Variant::Lava,
+            25 => Variant::Stone,
+            26 => Variant::Ice,
+            27 => Variant::Oil,
+            28 => Variant::Acid,
+            29 => Variant::Plant,
+            30 => Variant::Custom,
             _ => Variant::Empty,
         }
     }
@@ -106,9 +164,20 @@ impl Variant {
             Variant::NITR => update_nitrogen(particle, api),
             Variant::CO2 => update_co2(particle, api),
             Variant::WTVP => update_wtvp(particle, api),
-            Variant::GOL => update_gol(particle, api),
-            Variant::Empty => update_empty(particle, api),
+            Variant::GOL | Variant::HLIF | Variant::SEED | Variant::DNGT => {
+                update_life(particle, api)
+            }
             Variant::Glass => update_glass(particle, api),
+            Variant::BTRY => update_battery(particle, api),
+            Variant::SWCH => update_switch(particle, api),
+            Variant::WIRE => update_wire(particle, api),
+            Variant::Lava => update_lava(particle, api),
+            Variant::Stone => update_stone(particle, api),
+            Variant::Ice => update_ice(particle, api),
+            Variant::Oil => update_oil(particle, api),
+            Variant::Acid => update_acid(particle, api),
+            Variant::Plant => update_plant(particle, api),
+            Variant::Custom => elements::update(particle, api),
 
             _ => false,
         }
@@ -133,54 +202,27 @@ impl Variant {
             Variant::CO2 => "CO2",
             Variant::WTVP => "STM",
             Variant::GOL => "GOL",
+            Variant::HLIF => "HLIF",
+            Variant::SEED => "SEED",
+            Variant::DNGT => "DNGT",
+            Variant::BTRY => "BTRY",
+            Variant::SWCH => "SWCH",
+            Variant::WIRE => "WIRE",
+            Variant::Lava => "LAVA",
+            Variant::Stone => "STNE",
+            Variant::Ice => "ICE",
+            Variant::Oil => "OIL",
+            Variant::Acid => "ACID",
+            Variant::Plant => "PLNT",
             Variant::Glass => "GLAS",
+            Variant::Custom => "CUST",
         }
     }
 }
 
-fn update_empty(_particle: Particle, mut api: API) -> bool {
-    let mut alive_nbrs = 0;
-    if api.get(0, 1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(0, -1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(1, 0).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(-1, 0).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(1, 1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(-1, -1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(1, -1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if api.get(-1, 1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
-
-    if alive_nbrs == 3 {
-        api.set(0, 0, Particle::new(GOL, 0, 0));
-        return true;
-    }
-    false
-}
-
 fn update_sand(particle: Particle, mut api: API) -> bool {
-    if particle.temperature > 1700. {
+    if particle.temperature > SAND_MELT && !moved_away(&particle, &mut api) {
+        phase_change(&mut api, Variant::Sand, Variant::Glass);
         api.set(
             0,
             0,
@@ -199,83 +241,30 @@ fn update_glass(_particle: Particle, _api: API) -> bool {
 }
 
 fn update_salt(particle: Particle, mut api: API) -> bool {
-    // swap down with water if water above
-    let top = api.get(0, -1);
-    let bottom = api.get(0, 1);
-
-    if top.get_variant() == Variant::Water && bottom.get_variant() == Variant::Empty {
-        api.set(
-            0,
-            -1,
-            Particle {
-                variant_type: EMPTY,
-                ..top
-            },
-        );
-        api.set(
-            0,
-            1,
-            Particle {
-                variant_type: SALT_WATER,
-                ..particle
-            },
-        );
-        return true;
-    }
-
-    false
+    solution::dissolve(particle, &mut api)
 }
 
-fn update_salt_water(_particle: Particle, mut _api: API) -> bool {
-    // swap down with water if water above\
-    // if temp > 102, then turn into one part steam, one part salt
-    let top = _api.get(0, -1);
-    let bottom = _api.get(0, 1);
-
-    if _particle.temperature > 102. {
-        // turn into one part steam, one part salt
-        // check to see if there's room for steam AND salt, then split
-        // otherwise, just turn into salt
-        if top.get_variant() == Variant::Empty && bottom.get_variant() == Variant::Empty {
-            _api.set(
-                0,
-                -1,
-                Particle {
-                    variant_type: WTVP,
-                    .._particle
-                },
-            );
-            _api.set(
-                0,
-                1,
-                Particle {
-                    variant_type: SALT,
-                    .._particle
-                },
-            );
-        } else {
-            _api.set(
-                0,
-                0,
-                Particle {
-                    variant_type: SALT,
-                    .._particle
-                },
-            );
-        }
-
+fn update_salt_water(particle: Particle, mut api: API) -> bool {
+    if electricity::conduct(particle, &mut api) {
         return true;
     }
-    false
+    solution::update(particle, &mut api)
 }
 
-fn update_fire(mut particle: Particle, mut api: API) -> bool {
-    if api.once_per(50) && particle.dissolve_to(EMPTY) {
+pub(crate) const FIRE_O2_USE: f32 = 0.01;
+pub(crate) const LIFE_BREATH: f32 = 0.002;
+const PLANT_BREATH: f32 = 0.001;
+
+fn update_fire(particle: Particle, mut api: API) -> bool {
+    // smothered fires go straight to smoke
+    let starved = atmosphere::breathe(&mut api, Gas::O2, Gas::CO2, FIRE_O2_USE) < FIRE_O2_USE * 0.5;
+    if starved && !moved_away(&particle, &mut api) {
         api.set(
             0,
             0,
             Particle {
                 variant_type: SMOKE,
+                state: NO_STATE,
                 ..particle
             },
         );
@@ -287,50 +276,34 @@ fn update_fire(mut particle: Particle, mut api: API) -> bool {
 }
 
 fn update_water(particle: Particle, mut api: API) -> bool {
-    let _dx = api.rand_dir();
-    let _nbr = api.get(0, 1);
-
-    if particle.temperature > 100. {
-        api.set(
-            0,
-            0,
-            Particle {
-                variant_type: WTVP,
-                ..particle
-            },
-        );
-        return true;
-    }
+    solution::update(particle, &mut api)
+}
 
+fn update_smoke(_particle: Particle, _api: API) -> bool {
     false
 }
 
-fn update_smoke(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
-        return true;
-    }
-
+fn update_carbon(_particle: Particle, _api: API) -> bool {
     false
 }
 
-fn update_carbon(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
-        return false;
-    }
-    false
+fn update_iron(particle: Particle, mut api: API) -> bool {
+    electricity::conduct(particle, &mut api)
 }
 
-fn update_iron(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
-        return false;
-    }
-    false
+fn update_battery(_particle: Particle, mut api: API) -> bool {
+    electricity::power(&mut api)
+}
+
+fn update_switch(particle: Particle, mut api: API) -> bool {
+    electricity::conduct(particle, &mut api)
 }
 
 fn update_oxygen(particle: Particle, mut api: API) -> bool {
+    if api.world.atmosphere && atmosphere::dissolve(&particle, &mut api) {
+        return true;
+    }
+
     /*
     if api.once_in(10) && particle.dissolve_to(EMPTY) {
         api.set(0, 0, EMPTY_CELL);
@@ -343,26 +316,37 @@ fn update_oxygen(particle: Particle, mut api: API) -> bool {
 
     // if pressure is high, temp is high, and particle is right next to hydrogen, then, combine into water
 
-    let left = api.get(-1, 0);
-    let right = api.get(1, 0);
-    let up = api.get(0, -1);
-    let down = api.get(0, 1);
-
-    let mut nbrs = vec![left, right, up, down];
-
-    nbrs.sort_by(|a, b| a.temperature.partial_cmp(&b.temperature).unwrap());
+    if moved_away(&particle, &mut api) {
+        return false;
+    }
 
-    let mut nbr = nbrs[0];
+    // the coldest of the four edge neighbours, if it is hydrogen the two combine into water
+    // in its cell and the oxygen is used up
+    let (dx, dy, mut nbr) = EDGE_NBRS
+        .into_iter()
+        .map(|(dx, dy)| (dx, dy, api.get(dx, dy)))
+        .min_by(|a, b| a.2.temperature.total_cmp(&b.2.temperature))
+        .unwrap();
 
     if nbr.get_variant() == Variant::HYGN && nbr.dissolve_to(WATER) {
-        {
-            api.set(0, 0, nbr);
-            api.set(0, 1, particle);
-        }
+        api.set(dx, dy, nbr);
+        api.set(0, 0, EMPTY_CELL);
+        api.emit(Event::Reaction {
+            x: api.x,
+            y: api.y,
+            reactants: (Variant::OXGN, Variant::HYGN),
+            product: Variant::Water,
+        });
+        return true;
     }
 
     // if temperature high enough, burn into fire
-    if api.world.get_temperature(api.x, api.y) > 100. {
+    if api.world.get_temperature(api.x, api.y) > OXGN_IGNITION {
+        api.emit(Event::Ignition {
+            x: api.x,
+            y: api.y,
+            variant: Variant::OXGN,
+        });
         api.set(
             0,
             0,
@@ -378,106 +362,581 @@ fn update_oxygen(particle: Particle, mut api: API) -> bool {
 }
 
 fn update_wtvp(particle: Particle, mut api: API) -> bool {
-    if particle.temperature < 100. && particle.temperature > 0. {
+    let condenses = particle.temperature < STEAM_CONDENSE && particle.temperature > 0.;
+    if condenses && !moved_away(&particle, &mut api) {
+        // steam off boiled brine can carry a little salt back down
+        let concentration = api.world.get_f32(&particle, "concentration");
+        let water = solution::with_concentration(api.world, particle, concentration);
+        phase_change(&mut api, Variant::WTVP, water.get_variant());
+        api.set(0, 0, water);
+        return true;
+    }
+
+    // the air only takes up the water, salty steam stays a particle
+    if api.world.atmosphere && api.world.get_f32(&particle, "concentration") <= 0. {
+        return atmosphere::dissolve(&particle, &mut api);
+    }
+
+    false
+}
+
+// moore neighbours of the cell at (dx, dy) matching `pred`
+fn count_nbrs(api: &mut API, dx: i32, dy: i32, pred: impl Fn(&World, &Particle) -> bool) -> u32 {
+    let mut count = 0;
+    for nx in -1..=1 {
+        for ny in -1..=1 {
+            if (nx, ny) == (0, 0) {
+                continue;
+            }
+            let nbr = api.get(dx + nx, dy + ny);
+            if pred(api.world, &nbr) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+// shared by every life species, the rule comes from `World::life_rules`
+fn update_life(particle: Particle, mut api: API) -> bool {
+    let variant = particle.get_variant();
+    let rule = match api.world.life_rules.get(variant) {
+        Some(rule) => rule,
+        None => return false,
+    };
+
+    // live cells decide births in the empty cells around them,
+    // so empty space never has to be kept awake
+    let mut births = vec![];
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if (dx, dy) == (0, 0) || api.get(dx, dy).get_variant() != Variant::Empty {
+                continue;
+            }
+
+            // the species with the most neighbours wins a contested cell
+            let mut best: Option<(Variant, u32)> = None;
+            for i in 0..api.world.life_rules.len() {
+                let (species, species_rule) = api.world.life_rules.nth(i);
+                let n = count_nbrs(&mut api, dx, dy, |_, p| p.get_variant() == species);
+                if species_rule.born(n) && best.is_none_or(|(_, m)| n > m) {
+                    best = Some((species, n));
+                }
+            }
+
+            if let Some((species, _)) = best {
+                births.push((dx, dy, species));
+            }
+        }
+    }
+
+    let survives = rule.survives(count_nbrs(&mut api, 0, 0, |_, p| {
+        p.get_variant() == variant
+    }));
+
+    // with an atmosphere, life breathes and suffocates without oxygen
+    let suffocated = atmosphere::breathe(&mut api, Gas::O2, Gas::CO2, LIFE_BREATH) < LIFE_BREATH;
+
+    for (dx, dy, species) in births {
+        api.set(
+            dx,
+            dy,
+            Particle::new(*VariantType::from_variant(species), 0, 0),
+        );
+    }
+
+    if !survives || suffocated {
+        api.set(0, 0, EMPTY_CELL);
+    }
+
+    // get self temperature and die
+    if particle.temperature > LIFE_HEAT_DEATH {
         api.set(
             0,
             0,
             Particle {
-                variant_type: WATER,
+                variant_type: SAND,
                 ..particle
             },
         );
-        return true;
     }
+    true
+}
 
-    false
+// wireworld: head -> tail -> conductor, a conductor with one or two
+// neighbouring heads becomes a head. exact when the world is double buffered
+fn update_wire(mut particle: Particle, mut api: API) -> bool {
+    let next = match WireState::of(api.world, &particle).unwrap_or(WireState::Conductor) {
+        WireState::Head => WireState::Tail,
+        WireState::Tail => WireState::Conductor,
+        WireState::Conductor => {
+            let heads = count_nbrs(&mut api, 0, 0, |world, p| {
+                WireState::of(world, p) == Some(WireState::Head)
+            });
+            if heads == 1 || heads == 2 {
+                WireState::Head
+            } else {
+                return false;
+            }
+        }
+    };
+
+    api.set_field(&mut particle, "state", Value::U8(next as u8))
+        .unwrap();
+    api.set(0, 0, particle);
+    true
 }
 
-fn update_gol(particle: Particle, mut api: API) -> bool {
-    let mut alive_nbrs: u32 = 0;
+// the thresholds are shared with `probe`, which reports what a cell is about to do
+pub(crate) const SAND_MELT: f32 = 1700.;
+pub(crate) const OXGN_IGNITION: f32 = 100.;
+pub(crate) const STEAM_CONDENSE: f32 = 100.;
+pub(crate) const LIFE_HEAT_DEATH: f32 = 100.;
+pub(crate) const LAVA_SOLIDIFY: f32 = 800.;
+const LAVA_CONDUCTIVITY: f32 = 0.05;
+pub(crate) const STONE_MELT: f32 = 1100.;
+const ICE_WARMING: f32 = 0.005;
+const LAVA_COOLING: f32 = 0.002;
+const TEMPERATURE_DECAY: f32 = 0.01;
+pub(crate) const OIL_IGNITION: f32 = 250.;
+pub(crate) const EXPLOSIVE_IGNITION: f32 = 500.;
+pub const EXPLOSION_RADIUS: i32 = 2;
+const EXPLOSION_HEAT: f32 = 300.;
+pub(crate) const PLANT_IGNITION: f32 = 200.;
+// in the order oxygen looks for the coldest, the first of equally cold ones wins
+pub(crate) const EDGE_NBRS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const ACID_RATE: i32 = 4;
+const PLANT_GROWTH: i32 = 10;
+
+// a particle turning into another state of matter where it stands
+fn phase_change(api: &mut API, from: Variant, to: Variant) {
+    api.emit(Event::PhaseChange {
+        x: api.x,
+        y: api.y,
+        from,
+        to,
+    });
+}
 
-    if api.get(0, 1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
+// liquids and powders move before their update runs, in which case (0, 0) holds something else
+fn moved_away(particle: &Particle, api: &mut API) -> bool {
+    api.get(0, 0).get_variant() != particle.get_variant()
+}
 
-    if api.get(0, -1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
-    }
+// any of the eight neighbours matching `pred`
+fn touches(api: &mut API, pred: impl Fn(&Particle) -> bool) -> bool {
+    count_nbrs(api, 0, 0, |_, p| pred(p)) > 0
+}
 
-    if api.get(1, 0).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
+// explosive elements go off next to a flame or in hot air. the blast turns everything
+// within EXPLOSION_RADIUS into fire and heats the air there, walls are left standing
+pub(crate) fn detonate(particle: &Particle, api: &mut API) -> bool {
+    if !particle.variant_type.has_flag(FLAG_EXPLOSIVE) || moved_away(particle, api) {
+        return false;
     }
-
-    if api.get(-1, 0).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
+    let hot = api.world.get_temperature(api.x, api.y) > EXPLOSIVE_IGNITION;
+    if !hot && !touches(api, is_flame) {
+        return false;
     }
 
-    if api.get(1, 1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
+    api.emit(Event::Explosion {
+        x: api.x,
+        y: api.y,
+        variant: particle.get_variant(),
+        radius: EXPLOSION_RADIUS,
+    });
+    let r = EXPLOSION_RADIUS;
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if dx * dx + dy * dy > r * r || !api.inside(dx, dy) {
+                continue;
+            }
+            if api.get(dx, dy).variant_type.has_flag(FLAG_IMMUTABLE) {
+                continue;
+            }
+            api.set(dx, dy, Particle::new(FIRE, 0, 0));
+            let (x, y) = (api.x + dx, api.y + dy);
+            let heat = api.world.get_temperature(x, y) + EXPLOSION_HEAT;
+            api.world.set_temperature(x, y, heat);
+        }
     }
+    true
+}
+
+pub(crate) fn is_flame(particle: &Particle) -> bool {
+    matches!(particle.get_variant(), Variant::Fire | Variant::Lava)
+}
 
-    if api.get(-1, -1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
+// turn the particle at the given offset into fire
+fn ignite(api: &mut API, dx: i32, dy: i32, particle: Particle) -> bool {
+    api.emit(Event::Ignition {
+        x: api.x + dx,
+        y: api.y + dy,
+        variant: particle.get_variant(),
+    });
+    api.set(
+        dx,
+        dy,
+        Particle {
+            variant_type: FIRE,
+            strength: FIRE.strength,
+            ..particle
+        },
+    );
+    true
+}
+
+// where a particle's temperature drifts each tick, and how quickly. lava and ice only
+// start out at their base temperature, then cool or warm towards the surrounding air
+pub(crate) fn heat_target(particle: &Particle, ambient: f32) -> (f32, f32) {
+    match particle.get_variant() {
+        Variant::Lava => (ambient, LAVA_COOLING),
+        Variant::Ice => (ambient, ICE_WARMING),
+        _ => (particle.variant_type.base_temperature, TEMPERATURE_DECAY),
     }
+}
 
-    if api.get(1, -1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
+fn update_lava(mut particle: Particle, mut api: API) -> bool {
+    if moved_away(&particle, &mut api) {
+        return false;
     }
 
-    if api.get(-1, 1).get_variant() == Variant::GOL {
-        alive_nbrs += 1;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let nbr = api.get(dx, dy);
+            match nbr.get_variant() {
+                Variant::Empty | Variant::Wall | Variant::Lava => continue,
+                _ => (),
+            }
+
+            if nbr.variant_type.has_flag(FLAG_BURNS) && nbr.get_variant() != Variant::Fire {
+                ignite(&mut api, dx, dy, nbr);
+                continue;
+            }
+
+            // give heat to whatever it touches, the surface crusts over first
+            let transfer = (particle.temperature - nbr.temperature) * LAVA_CONDUCTIVITY;
+            if transfer > 0. {
+                api.world.add_heat(api.x + dx, api.y + dy, transfer);
+                particle.temperature -= transfer;
+            }
+        }
     }
 
-    if alive_nbrs < 2 || alive_nbrs > 3 {
-        api.set(0, 0, EMPTY_LL);
+    if particle.temperature < LAVA_SOLIDIFY {
+        phase_change(&mut api, Variant::Lava, Variant::Stone);
+        particle.variant_type = STONE;
     }
+    api.set(0, 0, particle);
+    true
+}
 
-    // get self temperature and die
-    if particle.temperature > 100. {
+fn update_stone(particle: Particle, mut api: API) -> bool {
+    if particle.temperature > STONE_MELT && !moved_away(&particle, &mut api) {
+        phase_change(&mut api, Variant::Stone, Variant::Lava);
         api.set(
             0,
             0,
             Particle {
-                variant_type: SAND,
+                variant_type: LAVA,
                 ..particle
             },
         );
+        return true;
+    }
+    false
+}
+
+fn update_ice(mut particle: Particle, mut api: API) -> bool {
+    // solids don't move, so the drift towards the air is only kept by writing it back
+    if particle.temperature > 0. {
+        phase_change(&mut api, Variant::Ice, Variant::Water);
+        particle.variant_type = WATER;
     }
+    api.set(0, 0, particle);
     true
 }
 
-fn update_hydrogen(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
+fn update_oil(particle: Particle, mut api: API) -> bool {
+    if moved_away(&particle, &mut api) {
         return false;
     }
+
+    if particle.temperature > OIL_IGNITION || touches(&mut api, is_flame) {
+        return ignite(&mut api, 0, 0, particle);
+    }
     false
 }
 
-fn update_helium(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
-        return false;
+// acid eats solids and powders, except glass which is what you keep it in
+pub(crate) fn dissolvable(particle: &Particle) -> bool {
+    match particle.get_variant() {
+        Variant::Empty | Variant::Glass | Variant::Acid => false,
+        _ => {
+            !particle.variant_type.has_flag(FLAG_IMMUTABLE)
+                && matches!(
+                    particle.variant_type.variant_property,
+                    VariantProperty::Solid | VariantProperty::Powder
+                )
+        }
     }
-    false
 }
 
-fn update_nitrogen(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
+fn update_acid(mut particle: Particle, mut api: API) -> bool {
+    if moved_away(&particle, &mut api) {
         return false;
     }
-    false
-}
 
-fn update_co2(mut particle: Particle, mut api: API) -> bool {
-    if api.once_in(10) && particle.dissolve_to(EMPTY) {
-        api.set(0, 0, EMPTY_CELL);
+    let (dx, dy) = api.rand_vec();
+    let nbr = api.get(dx, dy);
+    if !dissolvable(&nbr) || !api.once_in(ACID_RATE) {
         return false;
     }
+
+    api.set(dx, dy, EMPTY_CELL);
+    api.emit(Event::Reaction {
+        x: api.x + dx,
+        y: api.y + dy,
+        reactants: (Variant::Acid, nbr.get_variant()),
+        product: Variant::Empty,
+    });
+
+    // strength counts uses, the acid is spent once it reaches the element's strength
+    particle.strength += 1;
+    if particle.strength >= ACID.strength {
+        api.set(0, 0, EMPTY_CELL);
+    } else {
+        api.set(0, 0, particle);
+    }
+    true
+}
+
+fn update_plant(particle: Particle, mut api: API) -> bool {
+    if particle.temperature > PLANT_IGNITION || touches(&mut api, is_flame) {
+        return ignite(&mut api, 0, 0, particle);
+    }
+
+    // photosynthesis, turns co2 around it back into oxygen
+    atmosphere::breathe(&mut api, Gas::CO2, Gas::O2, PLANT_BREATH);
+
+    let (dx, dy) = api.rand_vec();
+    if api.get(dx, dy).get_variant() == Variant::Water && api.once_in(PLANT_GROWTH) {
+        api.set(dx, dy, Particle::new(PLANT, 0, 0));
+        return true;
+    }
+    false
+}
+
+fn update_hydrogen(_particle: Particle, _api: API) -> bool {
+    false
+}
+
+fn update_helium(_particle: Particle, _api: API) -> bool {
     false
 }
 
+fn update_nitrogen(particle: Particle, mut api: API) -> bool {
+    api.world.atmosphere && atmosphere::dissolve(&particle, &mut api)
+}
+
+fn update_co2(particle: Particle, mut api: API) -> bool {
+    api.world.atmosphere && atmosphere::dissolve(&particle, &mut api)
+}
+
 pub fn particle_to_color(variant_type: VariantType) -> (u8, u8, u8, u8) {
     variant_type.color.to_rgba8()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wire_states(world: &World, y: i32) -> Vec<WireState> {
+        (0..world.width)
+            .filter_map(|x| WireState::of(world, &world.get_particle(x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn test_wire_electron_travels() {
+        let mut world = World::new(8, 3);
+        world.set_double_buffered(true);
+        for x in 0..8 {
+            world.set_particle(x, 1, Variant::WIRE);
+        }
+        world.set_wire_state(1, 1, WireState::Tail);
+        world.set_wire_state(2, 1, WireState::Head);
+
+        for _ in 0..3 {
+            world.tick();
+        }
+
+        use WireState::*;
+        let expected = vec![
+            Conductor, Conductor, Conductor, Conductor, Tail, Head, Conductor, Conductor,
+        ];
+        assert_eq!(wire_states(&world, 1), expected);
+    }
+
+    #[test]
+    fn test_wire_diode() {
+        // a conductor with three heads around it stays put, which is what diodes are built from
+        let mut world = World::new(5, 5);
+        world.set_double_buffered(true);
+        world.set_particle(2, 2, Variant::WIRE);
+        for x in 1..4 {
+            world.set_particle(x, 1, Variant::WIRE);
+            world.set_wire_state(x, 1, WireState::Head);
+        }
+        world.tick();
+        assert_eq!(
+            WireState::of(&world, &world.get_particle(2, 2)),
+            Some(WireState::Conductor)
+        );
+    }
+
+    fn count(world: &World, variant: Variant) -> usize {
+        let mut n = 0;
+        for x in 0..world.width {
+            for y in 0..world.height {
+                if world.get_particle(x, y).get_variant() == variant {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    #[test]
+    fn test_lava_cools_into_stone() {
+        // one cell wide shaft so the lava can only sit on the sand
+        let mut world = World::new(1, 4);
+        world.set_particle(0, 2, Variant::Lava);
+        world.set_particle(0, 3, Variant::Sand);
+        for _ in 0..100 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Lava), 0);
+        assert_eq!(count(&world, Variant::Stone), 1);
+        assert!(world.get_particle(0, 3).temperature > SAND.base_temperature);
+    }
+
+    #[test]
+    fn test_lava_cools_in_the_open() {
+        // nothing to give its heat to, it cools towards the air instead
+        let mut world = World::new(3, 3);
+        for x in 0..3 {
+            world.set_particle(x, 2, Variant::Wall);
+        }
+        world.set_particle(1, 1, Variant::Lava);
+        for _ in 0..150 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Lava), 1);
+        for _ in 0..150 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Lava), 0);
+        assert_eq!(count(&world, Variant::Stone), 1);
+    }
+
+    #[test]
+    fn test_stone_melts() {
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 2, Variant::Stone);
+        world.add_heat(1, 2, STONE_MELT);
+        world.tick();
+        assert_eq!(world.get_particle(1, 2).get_variant(), Variant::Lava);
+    }
+
+    #[test]
+    fn test_new_particles_start_at_base_temperature() {
+        // they used to start at 0 and drift up to their base temperature
+        for variant_type in [SAND, WATER, LAVA, ICE] {
+            let particle = Particle::new(variant_type, 0, 0);
+            assert_eq!(particle.temperature, variant_type.base_temperature);
+        }
+    }
+
+    #[test]
+    fn test_lava_ignites_burnables() {
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 1, Variant::Plant);
+        world.set_particle(1, 2, Variant::Lava);
+        world.tick();
+        assert_eq!(count(&world, Variant::Plant), 0);
+    }
+
+    #[test]
+    fn test_ice_melts() {
+        let mut world = World::new(3, 3);
+        world.set_particle(1, 1, Variant::Ice);
+        world.tick();
+        assert_eq!(world.get_particle(1, 1).get_variant(), Variant::Ice);
+        for _ in 0..500 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Ice), 0);
+        assert_eq!(count(&world, Variant::Water), 1);
+    }
+
+    #[test]
+    fn test_oil_burns() {
+        // lid on top so the fire can't float away
+        let mut world = World::new(3, 3);
+        for x in 0..3 {
+            world.set_particle(x, 1, Variant::Wall);
+        }
+        world.set_particle(0, 2, Variant::Oil);
+        world.set_particle(1, 2, Variant::Oil);
+        world.set_particle(2, 2, Variant::Fire);
+        for _ in 0..5 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Oil), 0);
+    }
+
+    #[test]
+    fn test_acid_dissolves_and_weakens() {
+        let mut world = World::new(3, 20);
+        world.set_particle(1, 0, Variant::Acid);
+        for y in 1..20 {
+            for x in 0..3 {
+                world.set_particle(x, y, Variant::Sand);
+            }
+        }
+        for _ in 0..500 {
+            world.tick();
+        }
+        // a single drop is spent after eating ACID.strength cells
+        assert_eq!(count(&world, Variant::Acid), 0);
+        assert_eq!(count(&world, Variant::Sand), 57 - ACID.strength as usize);
+    }
+
+    #[test]
+    fn test_glass_resists_acid() {
+        let mut world = World::new(3, 3);
+        for x in 0..3 {
+            world.set_particle(x, 2, Variant::Glass);
+        }
+        world.set_particle(1, 1, Variant::Acid);
+        for _ in 0..100 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Glass), 3);
+    }
+
+    #[test]
+    fn test_plant_grows_into_water() {
+        let mut world = World::new(4, 4);
+        for x in 0..4 {
+            world.set_particle(x, 3, Variant::Water);
+        }
+        world.set_particle(0, 2, Variant::Plant);
+        for _ in 0..2000 {
+            world.tick();
+        }
+        assert_eq!(count(&world, Variant::Water), 0);
+        assert_eq!(count(&world, Variant::Plant), 5);
+    }
+}
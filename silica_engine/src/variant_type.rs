@@ -1,8 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use crate::variant::Variant;
+use crate::{lifetime::Lifetime, variant::Variant};
 
-pub const VARIANT_COUNT: usize = 18;
+pub const VARIANT_COUNT: usize = 31;
 use crate::colors::*;
 
 #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
@@ -14,13 +14,59 @@ pub struct VariantType {
     pub base_temperature: f32,
     pub variant_property: VariantProperty,
     pub flags: u8,
+    // None for stable elements, see `lifetime`
+    pub lifetime: Option<Lifetime>,
+    // None for elements that don't light up their surroundings, see `render`
+    pub emission: Option<Emission>,
+    // 0 for built in elements, otherwise the id of a runtime element, see `elements`
+    pub custom: u16,
 } // flags
 
 pub const FLAG_BURNS: u8 = 0b00000001;
 pub const FLAG_EXPLOSIVE: u8 = 0b00000010;
 pub const FLAG_IMMUTABLE: u8 = 0b00000100;
 pub const FLAG_IGNITES: u8 = 0b00001000;
+// cellular automaton, reads neighbours from the previous frame when the world is double buffered
+pub const FLAG_AUTOMATON: u8 = 0b00010000;
 pub const FLAG_ALIVE: u8 = 0b00100000;
+pub const FLAG_CONDUCTS: u8 = 0b01000000;
+
+// light an element gives off in the glow pass of `render`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Emission {
+    pub color: ParticleColor,
+    // added to the cell itself at full brightness, 1 saturates
+    pub intensity: f32,
+    // cells the light reaches, fading out with distance
+    pub radius: i32,
+    // glows in the colour of its temperature instead, once that is hot enough to see
+    pub incandescent: bool,
+}
+
+impl Emission {
+    pub const fn new(color: ParticleColor, intensity: f32, radius: i32) -> Emission {
+        Emission {
+            color,
+            intensity,
+            radius,
+            incandescent: false,
+        }
+    }
+
+    pub const fn incandescent(intensity: f32, radius: i32) -> Emission {
+        Emission {
+            color: ParticleColor {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            },
+            intensity,
+            radius,
+            incandescent: true,
+        }
+    }
+}
 
 impl VariantType {
     pub fn has_flag(&self, flag: u8) -> bool {
@@ -46,7 +92,20 @@ impl VariantType {
             Variant::CO2 => &CO2,
             Variant::WTVP => &WTVP,
             Variant::GOL => &GOL,
+            Variant::HLIF => &HLIF,
+            Variant::SEED => &SEED,
+            Variant::DNGT => &DNGT,
+            Variant::BTRY => &BTRY,
+            Variant::SWCH => &SWCH,
+            Variant::WIRE => &WIRE,
+            Variant::Lava => &LAVA,
+            Variant::Stone => &STONE,
+            Variant::Ice => &ICE,
+            Variant::Oil => &OIL,
+            Variant::Acid => &ACID,
+            Variant::Plant => &PLANT,
             Variant::Glass => &GLASS,
+            Variant::Custom => &CUSTOM,
         }
     }
 }
@@ -78,6 +137,46 @@ impl VariantProperty {
             VariantProperty::Gas => 3,
         }
     }
+
+    // as written in scripts and element files, ignoring case
+    pub fn from_name(name: &str) -> Result<VariantProperty, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "solid" => Ok(VariantProperty::Solid),
+            "powder" => Ok(VariantProperty::Powder),
+            "liquid" => Ok(VariantProperty::Liquid),
+            "gas" => Ok(VariantProperty::Gas),
+            _ => Err(format!("unknown property {:?}", name)),
+        }
+    }
+}
+
+// the ones data defined elements may set come first
+pub const FLAG_NAMES: [(u8, &str); 7] = [
+    (FLAG_BURNS, "burns"),
+    (FLAG_EXPLOSIVE, "explosive"),
+    (FLAG_IMMUTABLE, "immutable"),
+    (FLAG_IGNITES, "ignites"),
+    (FLAG_CONDUCTS, "conducts"),
+    (FLAG_AUTOMATON, "automaton"),
+    (FLAG_ALIVE, "alive"),
+];
+
+// the flags data defined elements may set, ignoring case
+pub fn flag_from_name(name: &str) -> Result<u8, String> {
+    FLAG_NAMES[..5]
+        .iter()
+        .find(|(_, flag)| flag.eq_ignore_ascii_case(name))
+        .map(|(bit, _)| *bit)
+        .ok_or_else(|| format!("unknown flag {:?}", name))
+}
+
+// the names of the flags set in `flags`
+pub fn flag_names(flags: u8) -> Vec<&'static str> {
+    FLAG_NAMES
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
 }
 
 pub fn get_color(variant: Variant) {
@@ -100,6 +199,19 @@ pub fn get_color(variant: Variant) {
         Variant::WTVP => STEAM_COLOR,
         Variant::Glass => GLASS_COLOR,
         Variant::GOL => ParticleColor::from_rgba((0, 0, 0, 0)),
+        Variant::HLIF => HIGHLIFE_COLOR,
+        Variant::SEED => SEEDS_COLOR,
+        Variant::DNGT => DAY_NIGHT_COLOR,
+        Variant::BTRY => BATTERY_COLOR,
+        Variant::SWCH => SWITCH_COLOR,
+        Variant::WIRE => WIRE_COLOR,
+        Variant::Lava => LAVA_COLOR,
+        Variant::Stone => STONE_COLOR,
+        Variant::Ice => ICE_COLOR,
+        Variant::Oil => OIL_COLOR,
+        Variant::Acid => ACID_COLOR,
+        Variant::Plant => PLANT_COLOR,
+        Variant::Custom => CUSTOM_COLOR,
     };
 }
 
@@ -122,6 +234,19 @@ pub fn get_variant(color: ParticleColor) -> Variant {
         CO2_COLOR => Variant::CO2,
         STEAM_COLOR => Variant::WTVP,
         GLASS_COLOR => Variant::Glass,
+        HIGHLIFE_COLOR => Variant::HLIF,
+        SEEDS_COLOR => Variant::SEED,
+        DAY_NIGHT_COLOR => Variant::DNGT,
+        BATTERY_COLOR => Variant::BTRY,
+        SWITCH_COLOR => Variant::SWCH,
+        WIRE_COLOR => Variant::WIRE,
+        LAVA_COLOR => Variant::Lava,
+        STONE_COLOR => Variant::Stone,
+        ICE_COLOR => Variant::Ice,
+        OIL_COLOR => Variant::Oil,
+        ACID_COLOR => Variant::Acid,
+        PLANT_COLOR => Variant::Plant,
+        CUSTOM_COLOR => Variant::Custom,
         _ => Variant::Empty,
     };
     c
@@ -135,16 +260,9 @@ pub const EMPTY: VariantType = VariantType {
     flags: 0,
     variant_property: VariantProperty::Solid,
     base_temperature: 22.,
-};
-
-pub const EMPTY_L: VariantType = VariantType {
-    weight: 0,
-    strength: 0,
-    color: EMPTY_COLOR,
-    source_variant: Variant::Empty,
-    flags: FLAG_ALIVE,
-    variant_property: VariantProperty::Solid,
-    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const WALL: VariantType = VariantType {
@@ -155,6 +273,9 @@ pub const WALL: VariantType = VariantType {
     flags: FLAG_IMMUTABLE,
     variant_property: VariantProperty::Solid,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const SAND: VariantType = VariantType {
@@ -165,6 +286,9 @@ pub const SAND: VariantType = VariantType {
     variant_property: VariantProperty::Powder,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const WATER: VariantType = VariantType {
@@ -175,36 +299,48 @@ pub const WATER: VariantType = VariantType {
     variant_property: VariantProperty::Liquid,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const FIRE: VariantType = VariantType {
-    weight: 64,
+    weight: 3,
     strength: 16,
     color: FIRE_COLOR,
     source_variant: Variant::Fire,
     variant_property: VariantProperty::Gas,
     flags: FLAG_BURNS,
     base_temperature: 422.,
+    lifetime: Some(Lifetime::new(20, 80, Variant::Smoke)),
+    emission: Some(Emission::new(FIRE_GLOW_COLOR, 0.5, 3)),
+    custom: 0,
 };
 
 pub const SMOKE: VariantType = VariantType {
-    weight: 1,
+    weight: 6,
     strength: 64,
     color: SMOKE_COLOR,
     source_variant: Variant::Smoke,
     variant_property: VariantProperty::Gas,
     flags: 0,
     base_temperature: 22.,
+    lifetime: Some(Lifetime::new(10, 40, Variant::Empty)),
+    emission: None,
+    custom: 0,
 };
 
 pub const SALT: VariantType = VariantType {
-    weight: 1,
+    weight: 60,
     strength: 0,
     color: SALT_COLOR,
     source_variant: Variant::Salt,
     variant_property: VariantProperty::Powder,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const SALT_WATER: VariantType = VariantType {
@@ -213,58 +349,76 @@ pub const SALT_WATER: VariantType = VariantType {
     color: SALT_WATER_COLOR,
     source_variant: Variant::SaltWater,
     variant_property: VariantProperty::Liquid,
-    flags: 0,
+    flags: FLAG_CONDUCTS,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const OXGN: VariantType = VariantType {
-    weight: 0,
+    weight: 8,
     strength: 0,
     color: OXYGEN_COLOR,
     source_variant: Variant::OXGN,
     variant_property: VariantProperty::Gas,
     flags: FLAG_BURNS | FLAG_IGNITES,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const HYGN: VariantType = VariantType {
-    weight: 0,
+    weight: 1,
     strength: 0,
     color: HYDROGEN_COLOR,
     source_variant: Variant::HYGN,
     variant_property: VariantProperty::Gas,
-    flags: FLAG_BURNS | FLAG_IGNITES,
+    flags: FLAG_BURNS | FLAG_IGNITES | FLAG_EXPLOSIVE,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const HELM: VariantType = VariantType {
-    weight: 0,
+    weight: 2,
     strength: 0,
     color: HELIUM_COLOR,
     source_variant: Variant::HELM,
     variant_property: VariantProperty::Gas,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const CARB: VariantType = VariantType {
-    weight: 0,
+    weight: 40,
     strength: 0,
     color: CARBON_COLOR,
     source_variant: Variant::CARB,
     variant_property: VariantProperty::Powder,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const NITR: VariantType = VariantType {
-    weight: 0,
+    weight: 7,
     strength: 0,
     color: NITROGEN_COLOR,
     source_variant: Variant::NITR,
     variant_property: VariantProperty::Gas,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const IRON: VariantType = VariantType {
@@ -273,28 +427,37 @@ pub const IRON: VariantType = VariantType {
     color: IRON_COLOR,
     source_variant: Variant::IRON,
     variant_property: VariantProperty::Solid,
-    flags: 0,
+    flags: FLAG_CONDUCTS,
     base_temperature: 22.,
+    lifetime: None,
+    emission: Some(Emission::incandescent(0.6, 2)),
+    custom: 0,
 };
 
 pub const CO2: VariantType = VariantType {
-    weight: 0,
+    weight: 11,
     strength: 0,
     color: CO2_COLOR,
     source_variant: Variant::CO2,
     variant_property: VariantProperty::Gas,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const WTVP: VariantType = VariantType {
-    weight: 0,
+    weight: 5,
     strength: 0,
     color: STEAM_COLOR,
     source_variant: Variant::WTVP,
     variant_property: VariantProperty::Gas,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const GOL: VariantType = VariantType {
@@ -308,8 +471,168 @@ pub const GOL: VariantType = VariantType {
     },
     source_variant: Variant::GOL,
     variant_property: VariantProperty::Solid,
+    flags: FLAG_AUTOMATON,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const HLIF: VariantType = VariantType {
+    weight: 0,
+    strength: 16,
+    color: HIGHLIFE_COLOR,
+    source_variant: Variant::HLIF,
+    variant_property: VariantProperty::Solid,
+    flags: FLAG_AUTOMATON,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const SEED: VariantType = VariantType {
+    weight: 0,
+    strength: 16,
+    color: SEEDS_COLOR,
+    source_variant: Variant::SEED,
+    variant_property: VariantProperty::Solid,
+    flags: FLAG_AUTOMATON,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const DNGT: VariantType = VariantType {
+    weight: 0,
+    strength: 16,
+    color: DAY_NIGHT_COLOR,
+    source_variant: Variant::DNGT,
+    variant_property: VariantProperty::Solid,
+    flags: FLAG_AUTOMATON,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const BTRY: VariantType = VariantType {
+    weight: 255,
+    strength: 0,
+    color: BATTERY_COLOR,
+    source_variant: Variant::BTRY,
+    variant_property: VariantProperty::Solid,
+    flags: 0,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const SWCH: VariantType = VariantType {
+    weight: 255,
+    strength: 0,
+    color: SWITCH_COLOR,
+    source_variant: Variant::SWCH,
+    variant_property: VariantProperty::Solid,
+    flags: FLAG_CONDUCTS,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const WIRE: VariantType = VariantType {
+    weight: 255,
+    strength: 0,
+    color: WIRE_COLOR,
+    source_variant: Variant::WIRE,
+    variant_property: VariantProperty::Solid,
+    flags: FLAG_AUTOMATON,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const LAVA: VariantType = VariantType {
+    weight: 80,
+    strength: 0,
+    color: LAVA_COLOR,
+    source_variant: Variant::Lava,
+    variant_property: VariantProperty::Liquid,
+    flags: FLAG_IGNITES,
+    base_temperature: 1200.,
+    lifetime: None,
+    emission: Some(Emission::new(LAVA_GLOW_COLOR, 0.4, 4)),
+    custom: 0,
+};
+
+pub const STONE: VariantType = VariantType {
+    weight: 96,
+    strength: 0,
+    color: STONE_COLOR,
+    source_variant: Variant::Stone,
+    variant_property: VariantProperty::Powder,
+    flags: 0,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const ICE: VariantType = VariantType {
+    weight: 30,
+    strength: 0,
+    color: ICE_COLOR,
+    source_variant: Variant::Ice,
+    variant_property: VariantProperty::Solid,
     flags: 0,
+    base_temperature: -10.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const OIL: VariantType = VariantType {
+    weight: 24,
+    strength: 0,
+    color: OIL_COLOR,
+    source_variant: Variant::Oil,
+    variant_property: VariantProperty::Liquid,
+    flags: FLAG_BURNS,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+// strength is how many cells a drop of acid can eat before it's spent
+pub const ACID: VariantType = VariantType {
+    weight: 34,
+    strength: 8,
+    color: ACID_COLOR,
+    source_variant: Variant::Acid,
+    variant_property: VariantProperty::Liquid,
+    flags: 0,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+pub const PLANT: VariantType = VariantType {
+    weight: 40,
+    strength: 0,
+    color: PLANT_COLOR,
+    source_variant: Variant::Plant,
+    variant_property: VariantProperty::Solid,
+    flags: FLAG_BURNS,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 pub const GLASS: VariantType = VariantType {
@@ -320,6 +643,23 @@ pub const GLASS: VariantType = VariantType {
     variant_property: VariantProperty::Solid,
     flags: 0,
     base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
+};
+
+// template for runtime elements, registering one fills in the rest
+pub const CUSTOM: VariantType = VariantType {
+    weight: 64,
+    strength: 0,
+    color: CUSTOM_COLOR,
+    source_variant: Variant::Custom,
+    variant_property: VariantProperty::Powder,
+    flags: 0,
+    base_temperature: 22.,
+    lifetime: None,
+    emission: None,
+    custom: 0,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -349,6 +689,29 @@ impl ParticleColor {
     }
 }
 
+// the colour of a blackbody at `temperature`, alpha is how bright it glows.
+// transparent below the draper point, white hot past the last stop
+pub fn incandescence(temperature: f32) -> ParticleColor {
+    if temperature <= DRAPER_POINT {
+        return EMPTY_COLOR;
+    }
+    let mut below = (DRAPER_POINT, EMPTY_COLOR);
+    for (stop, color) in INCANDESCENCE {
+        if temperature < stop {
+            let t = (temperature - below.0) / (stop - below.0);
+            let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+            return ParticleColor {
+                r: mix(below.1.r, color.r),
+                g: mix(below.1.g, color.g),
+                b: mix(below.1.b, color.b),
+                a: mix(below.1.a, color.a),
+            };
+        }
+        below = (stop, color);
+    }
+    below.1
+}
+
 pub struct HSV {
     pub h: f32,
     pub s: f32,
@@ -474,50 +837,13 @@ impl ParticleColor {
         self.b = (self.b as f32 * (1. - factor)) as u8;
     }
 
-    // whiten based on given temperature value
-    pub fn whiten(&mut self, temperature: f32) {
-        if temperature < 20. {
-            return;
-        }
-
-        // temperature must be above 900 C for this to happen gradually from original color to white
-        // dont just set to 255, gradually increase above 900C thesh
-        let mut white_factor = (temperature - 20.) / 1000.;
-        if white_factor > 1. {
-            white_factor = 1.;
-        }
-
-        self.r = (self.r as f32 * (1. - white_factor) + 255. * white_factor) as u8;
-        self.g = (self.g as f32 * (1. - white_factor) + 255. * white_factor) as u8;
-        self.b = (self.b as f32 * (1. - white_factor) + 255. * white_factor) as u8;
-    }
-
-    pub fn melting(&mut self, temperature: f32) -> ParticleColor {
-        // orangish color
-        let mut res = ParticleColor {
-            r: 255,
-            g: 128,
-            b: 0,
-            a: 255,
-        };
-
-        // if temperature is below melting point, dont change color
-        if temperature < 1000. {
-            return *self;
-        }
-
-        // temperature must be above 900 C for this to happen gradually from original color to white
-        // dont just set to 255, gradually increase above 900C thesh
-        let mut melt_factor = (temperature - 1000.) / 1000.;
-        if melt_factor > 1. {
-            melt_factor = 1.;
-        }
-
-        res.r = (self.r as f32 * (1. - melt_factor) + 255. * melt_factor) as u8;
-        res.g = (self.g as f32 * (1. - melt_factor) + 128. * melt_factor) as u8;
-        res.b = (self.b as f32 * (1. - melt_factor) + 0. * melt_factor) as u8;
-
-        res
+    // blend towards the glow of something this hot, see `incandescence`
+    pub fn incandesce(&mut self, temperature: f32) {
+        let glow = incandescence(temperature);
+        let factor = glow.a as f32 / 255.;
+        self.r = (self.r as f32 * (1. - factor) + glow.r as f32 * factor) as u8;
+        self.g = (self.g as f32 * (1. - factor) + glow.g as f32 * factor) as u8;
+        self.b = (self.b as f32 * (1. - factor) + glow.b as f32 * factor) as u8;
     }
 
     pub fn to_rgba8(&self) -> (u8, u8, u8, u8) {
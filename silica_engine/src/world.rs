@@ -1,28 +1,44 @@
 use std::{
+    cmp::Ordering,
     collections::HashSet,
-    io::{BufWriter, Read, Write},
+    io::{Read, Write},
     path::Path,
+    sync::Arc,
 };
 
 use crate::{
     api::API,
-    particle::{self, Particle},
-    variant::{Variant, EMPTY_CELL},
+    atmosphere::{self, GasMix},
+    electricity,
+    elements::{Behaviour, Elements},
+    events::{self, CountWatch, Event},
+    gravity::{self, Gravity, GravityPoint},
+    life::LifeRules,
+    lifetime,
+    metadata::{self, Field, StateBuffer, Value},
+    particle::{self, Particle, Velocity},
+    snapshot::{self, Snapshot},
+    variant::{self, Variant, WireState, EMPTY_CELL},
     variant_type,
 };
 
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use variant_type::{VariantProperty, VariantType, EMPTY, FLAG_ALIVE};
+use variant_type::{VariantProperty, VariantType, EMPTY, FLAG_ALIVE, FLAG_AUTOMATON};
 
 pub const GRAVITY: f32 = 10f32;
+const REST: Velocity = Velocity { x: 0., y: 0. };
 pub const SPREAD_FACTOR: f32 = 0.1f32;
+// gases lighter than this rise into empty space, heavier ones sink
+pub const AIR_WEIGHT: u8 = 8;
 
 #[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Environment {
     pub pressure: f32,
     pub ambient_temperature: f32,
     pub ambient_pressure: f32,
+    // only tracked when the world has an atmosphere
+    pub gas: GasMix,
 }
 #[derive(Serialize, Deserialize)]
 pub struct World {
@@ -35,6 +51,37 @@ pub struct World {
     pub modified_indices: HashSet<usize>,
     pub cleared: bool,
     pub modified_state: bool,
+    pub gravity: Gravity,
+    pub life_rules: LifeRules,
+    // when set, automata read the frame as it was at the start of the tick
+    pub double_buffered: bool,
+    #[serde(skip)]
+    pub(crate) previous: Vec<Particle>,
+    #[serde(skip)]
+    pub(crate) read_previous: bool,
+    // per cell gas mix, fire and life need oxygen when this is on
+    pub atmosphere: bool,
+    // when set, the edges read as empty space instead of wall and particles can fall out
+    pub open_edges: bool,
+    // what happened during the last tick, see `drain_events`
+    #[serde(skip)]
+    pub(crate) events: Vec<Event>,
+    #[serde(skip)]
+    pub(crate) watches: Vec<CountWatch>,
+    // runtime elements, these have to be registered again after loading a save
+    #[serde(skip)]
+    pub elements: Elements,
+    // element state particles point into, see `metadata`
+    pub(crate) state: StateBuffer,
+    // ticks so far and changes made to the particles from outside one, with the edit
+    // count the last tick started at. lets a renderer tell whether modified_indices
+    // still hold everything that changed since it last looked
+    #[serde(skip)]
+    pub(crate) ticks: u64,
+    #[serde(skip)]
+    pub(crate) edits: u64,
+    #[serde(skip)]
+    pub(crate) edits_at_tick: u64,
 }
 
 impl Default for World {
@@ -70,30 +117,21 @@ impl World {
         decoder.read_to_end(buf.as_mut()).unwrap();
 
         let world: World = bincode::deserialize(&buf).unwrap();
+        let (ticks, edits) = (self.ticks, self.edits);
         *self = world;
+        self.ticks = ticks;
+        self.edits = edits + 1;
+        self.cleared = true;
     }
     pub fn save(&self, path: &str) {
         let mut p = String::from(path);
 
-        let mut image = Vec::new();
-        for particle in self.particles.iter() {
-            let color = particle::particle_to_color(*particle).to_rgb8();
-            image.push(color.0);
-            image.push(color.1);
-            image.push(color.2);
-        }
-
         // add png extension if not present
         if !p.ends_with(".png") {
             p.push_str(".png");
         }
 
-        let ref mut w = BufWriter::new(File::create(p).unwrap());
-        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
-        encoder.set_color(png::ColorType::Rgb);
-        encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = encoder.write_header().unwrap();
-        writer.write_image_data(&image).unwrap(); // Save
+        snapshot::save_png(self, &Snapshot::default(), &p).unwrap();
         println!("saving to {}.png", path);
     }
 
@@ -115,11 +153,21 @@ impl World {
                 i += 3;
             }
         }
+        self.edits += 1;
+        self.cleared = true;
     }
 
     pub fn tick(&mut self) {
         self.cleared = false;
+        self.events.clear();
+        self.ticks += 1;
+        self.edits_at_tick = self.edits;
+        // kept until the next tick so renderers can show what changed
+        self.modified_indices.clear();
         if self.running {
+            if self.double_buffered {
+                self.previous.clone_from(&self.particles);
+            }
             /*
                         for x in 0..self.width {
                             for y in 0..self.height {
@@ -216,8 +264,16 @@ impl World {
                         continue;
                     }
 
+                    // already moved this tick, don't carry it along in the scan direction
+                    if particle.clock == self.generation.wrapping_add(1) {
+                        continue;
+                    }
+
                     // self.distribute_cell_temperature(x, y);
 
+                    self.read_previous =
+                        self.double_buffered && particle.variant_type.has_flag(FLAG_AUTOMATON);
+
                     self.modified_state = World::update_particle(
                         particle,
                         API {
@@ -226,17 +282,32 @@ impl World {
                             y: y as i32,
                         },
                     );
-                    if self.modified_state {
+                    // something moved out of or reacted in this cell
+                    let replaced = self.particles[idx].get_variant() != particle.get_variant();
+                    if self.modified_state || replaced {
                         self.modified_indices.insert(idx);
                     }
                 } //end gen
             }
 
+            self.read_previous = false;
+
+            if self.atmosphere {
+                atmosphere::step(self);
+            }
+
+            if !self.watches.is_empty() {
+                events::check_counts(self);
+            }
+
+            // state only the particles still in the world point to is kept
+            self.state
+                .sweep(self.particles.iter().chain(self.previous.iter()));
+
             // ambient heat calculation
             // take a 3x3 grid and share temp based on center temp until all cells are the same
 
             self.generation = self.generation.wrapping_add(1);
-            self.modified_indices.clear();
         }
     }
 
@@ -265,65 +336,59 @@ impl World {
             return false;
         }
 
-        // decrease temperature over time to variant base temperature
-        let temperature = particle.temperature;
-        let base_temperature = particle.variant_type.base_temperature;
-        let temperature_decay_rate = 0.01; // Adjust this rate based on your preference
+        // velocity is the step taken this tick, whatever doesn't move comes to rest
+        if particle.velocity != REST {
+            particle.velocity = REST;
+            api.set(0, 0, particle);
+        }
 
-        particle.temperature += (base_temperature - temperature) * temperature_decay_rate;
+        // drift towards the temperature the element settles at
+        let ambient = api.world.get_temperature(api.x, api.y);
+        let (target, rate) = variant::heat_target(&particle, ambient);
+        particle.temperature += (target - particle.temperature) * rate;
+        if lifetime::age(&mut particle, &mut api) {
+            return true;
+        }
+        if variant::detonate(&particle, &mut api) {
+            return true;
+        }
         let _type = particle.variant_type;
+        // "down" follows the gravity field, weightless cells only run their element rules
+        let g = api.gravity();
         match particle.variant_type.variant_property {
+            _ if g == (0, 0) => (),
             VariantProperty::Powder => {
                 let dx = api.rand_dir();
-                let nbr = api.get(0, 1);
+                let (sx, sy) = gravity::spread(g, dx);
+                let nbr = api.get(g.0, g.1);
 
                 if nbr.get_variant() == Variant::Empty {
-                    api.set(dx, 1, particle);
+                    // jitter sideways while falling, but only into free cells
+                    let (tx, ty) = if api.get(sx, sy).get_variant() == Variant::Empty {
+                        (sx, sy)
+                    } else {
+                        g
+                    };
+                    api.set(tx, ty, World::stepped(particle, tx, ty));
                     api.set(0, 0, EMPTY_CELL);
-                } else if api.get(dx, 1).get_variant() == Variant::Empty {
-                    api.set(dx, 1, particle);
+                } else if api.get(sx, sy).get_variant() == Variant::Empty {
+                    api.set(sx, sy, World::stepped(particle, sx, sy));
                     api.set(0, 0, EMPTY_CELL);
-                } else if nbr.variant_type.variant_property == VariantProperty::Liquid {
-                    api.set(0, 0, nbr);
-                    api.set(0, 1, particle);
-                } else {
-                    let weight_diff = particle
-                        .variant_type
-                        .weight
-                        .wrapping_sub(nbr.variant_type.weight);
-                    let max_velocity = 5;
-                    let velocity = weight_diff.min(max_velocity);
-
-                    for _ in 0..velocity {
-                        if api.get(0, 1).get_variant() == Variant::Empty {
-                            api.set(0, 1, particle);
-                            api.set(0, 0, EMPTY_CELL);
-                            break;
-                        } else if api.get(dx, 1).get_variant() == Variant::Empty {
-                            api.set(dx, 1, particle);
-                            api.set(0, 0, EMPTY_CELL);
-                            break;
-                        } else if api.get(0, 1).variant_type.variant_property
-                            == VariantProperty::Liquid
-                        {
-                            api.set(0, 0, nbr);
-                            api.set(0, 1, particle);
-                            break;
-                        } else {
-                            api.set(0, 0, particle);
-                            break;
-                        }
-                    }
+                } else if !World::sink(&mut api, particle, g.0, g.1)
+                    && !World::sink(&mut api, particle, sx, sy)
+                {
+                    api.set(0, 0, particle);
                 }
             }
 
             VariantProperty::Liquid => {
                 let mut dx = api.rand_dir();
-                let below = api.get(0, 1);
-                let dx1 = api.get(dx, 1);
-                if particle.variant_type.weight > below.variant_type.weight {
-                    //swap
-                    api.swap(0, 1);
+                let (sx, sy) = gravity::spread(g, dx);
+                let (rx, ry) = gravity::spread(g, -dx);
+                let below = api.get(g.0, g.1);
+                let dx1 = api.get(sx, sy);
+                if World::sink(&mut api, particle, g.0, g.1) {
+                    return true;
                 }
 
                 // let mut dx0 = api.get(dx, 0);
@@ -335,45 +400,67 @@ impl World {
                         //randomize direction when falling sometimes
                         ra = 100 + api.rand_int(50) as u8;
                     }
-                    api.set(0, 1, Particle { ra, ..particle });
+                    api.set(
+                        g.0,
+                        g.1,
+                        Particle {
+                            ra,
+                            ..World::stepped(particle, g.0, g.1)
+                        },
+                    );
 
                     return true;
                 } else if dx1.get_variant() == Variant::Empty {
                     //fall diagonally
                     api.set(0, 0, dx1);
-                    api.set(dx, 1, particle);
+                    api.set(sx, sy, World::stepped(particle, sx, sy));
                     return true;
-                } else if api.get(-dx, 1).get_variant() == Variant::Empty {
+                } else if api.get(rx, ry).get_variant() == Variant::Empty {
                     api.set(0, 0, EMPTY_CELL);
-                    api.set(-dx, 1, particle);
+                    api.set(rx, ry, World::stepped(particle, rx, ry));
                     return true;
                 }
                 let left = particle.ra % 2 == 0;
                 dx = if left { 1 } else { -1 };
-                let dx0 = api.get(dx, 0);
-                let dxd = api.get(dx * 2, 0);
+                let (hx, hy) = gravity::side(g, dx);
+                let dx0 = api.get(hx, hy);
+                let dxd = api.get(hx * 2, hy * 2);
 
                 if dx0.get_variant() == Variant::Empty && dxd.get_variant() == Variant::Empty {
                     // scoot double
                     api.set(0, 0, dxd);
-                    api.set(2 * dx, 0, Particle { rb: 6, ..particle });
+                    api.set(
+                        2 * hx,
+                        2 * hy,
+                        Particle {
+                            rb: 6,
+                            ..World::stepped(particle, 2 * hx, 2 * hy)
+                        },
+                    );
                     let (dx, dy) = api.rand_vec(); //rand_vec_8
                     let nbr = api.get(dx, dy);
 
-                    // spread opinion
+                    // spread opinion, only the direction is passed on
                     if nbr.get_variant() == Variant::Water && nbr.ra % 2 != particle.ra % 2 {
                         api.set(
                             dx,
                             dy,
                             Particle {
                                 ra: particle.ra,
-                                ..particle
+                                ..nbr
                             },
                         )
                     }
                 } else if dx0.get_variant() == Variant::Empty {
                     api.set(0, 0, dx0);
-                    api.set(dx, 0, Particle { rb: 3, ..particle });
+                    api.set(
+                        hx,
+                        hy,
+                        Particle {
+                            rb: 3,
+                            ..World::stepped(particle, hx, hy)
+                        },
+                    );
                     let (dx, dy) = api.rand_vec(); //rand_vec_8
                     let nbr = api.get(dx, dy);
                     if nbr.get_variant() == Variant::Water {
@@ -383,13 +470,14 @@ impl World {
                                 dy,
                                 Particle {
                                     ra: particle.ra,
-                                    ..particle
+                                    ..nbr
                                 },
                             )
                         }
                     }
                 } else if particle.rb == 0 {
-                    if api.get(-dx, 0).get_variant() == Variant::Empty {
+                    let (ox, oy) = gravity::side(g, -dx);
+                    if api.get(ox, oy).get_variant() == Variant::Empty {
                         // bump
                         api.set(
                             0,
@@ -412,27 +500,27 @@ impl World {
                     );
                 }
 
-                // weight distribution
-                let dx = api.rand_dir();
-                let nbr = api.get(dx, 1);
-                let weight = particle.variant_type.weight;
-                let nbr_weight = nbr.variant_type.weight;
-
-                if nbr_weight < weight {
-                    // swap
-                    api.swap(0, 1);
+                // weight distribution, only if it didn't just flow sideways
+                if api.get(0, 0).get_variant() == particle.get_variant() {
+                    let dx = api.rand_dir();
+                    let (sx, sy) = gravity::spread(g, dx);
+                    World::sink(&mut api, particle, sx, sy);
                 }
             }
 
             VariantProperty::Gas => {
-                // basic sand behavior but upwards
+                // lighter than air rises, heavier than air pools, the rest drifts
                 let dx = api.rand_dir();
-                let nbr = api.get(dx, -1);
+                let (mx, my) = match particle.variant_type.weight.cmp(&AIR_WEIGHT) {
+                    Ordering::Less => gravity::spread((-g.0, -g.1), dx),
+                    Ordering::Greater => gravity::spread(g, dx),
+                    Ordering::Equal => api.rand_vec(),
+                };
 
-                if api.get(dx, -1).get_variant() == Variant::Empty {
-                    api.set(dx, -1, particle);
+                if api.get(mx, my).get_variant() == Variant::Empty {
+                    api.set(mx, my, World::stepped(particle, mx, my));
                     api.set(0, 0, EMPTY_CELL);
-                } else {
+                } else if !World::sink(&mut api, particle, g.0, g.1) {
                     api.set(0, 0, particle);
                 }
             }
@@ -444,13 +532,40 @@ impl World {
         *state
     }
 
+    // density displacement: sink into a lighter liquid or gas at (dx, dy), which floats up in exchange
+    fn sink(api: &mut API, particle: Particle, dx: i32, dy: i32) -> bool {
+        let nbr = api.get(dx, dy);
+        let fluid = matches!(
+            nbr.variant_type.variant_property,
+            VariantProperty::Liquid | VariantProperty::Gas
+        );
+        if !fluid || nbr.variant_type.weight >= particle.variant_type.weight {
+            return false;
+        }
+
+        api.set(0, 0, World::stepped(nbr, -dx, -dy));
+        api.set(dx, dy, World::stepped(particle, dx, dy));
+        true
+    }
+
+    // the particle after a step of (dx, dy), which is what its velocity holds
+    fn stepped(particle: Particle, dx: i32, dy: i32) -> Particle {
+        Particle {
+            velocity: Velocity {
+                x: dx as f32,
+                y: dy as f32,
+            },
+            ..particle
+        }
+    }
+
     fn paint_variants(&mut self) {
         for x in 0..self.width {
             for y in 0..self.height {
                 let idx = self.get_idx(x, y);
                 let particle = self.get_particle(x, y);
                 let _variant = particle.get_variant();
-                let color = particle::particle_to_color(particle).to_rgba8();
+                let color = particle::particle_to_color(self, particle).to_rgba8();
                 self.particles[idx].ra = color.0;
                 self.particles[idx].rb = color.1;
                 self.particles[idx].clock = self.generation;
@@ -464,6 +579,7 @@ impl World {
                 pressure: 0.,
                 ambient_temperature: 22.,
                 ambient_pressure: 0.,
+                gas: GasMix::default(),
             })
             .collect();
         World {
@@ -476,6 +592,20 @@ impl World {
             modified_indices: HashSet::new(),
             cleared: false,
             modified_state: false,
+            gravity: Gravity::default(),
+            life_rules: LifeRules::default(),
+            double_buffered: false,
+            previous: Vec::new(),
+            read_previous: false,
+            atmosphere: false,
+            open_edges: false,
+            events: Vec::new(),
+            watches: Vec::new(),
+            elements: Elements::default(),
+            state: StateBuffer::default(),
+            ticks: 0,
+            edits: 0,
+            edits_at_tick: 0,
         }
     }
 
@@ -488,8 +618,16 @@ impl World {
             *particle = Particle::new(EMPTY, 0, 0);
         }
 
+        self.state = StateBuffer::default();
         self.cleared = true;
         self.modified_indices.clear();
+        self.edits += 1;
+    }
+
+    // a change to the particle at idx, picked up by renderers and stats
+    fn touch(&mut self, idx: usize) {
+        self.modified_indices.insert(idx);
+        self.edits += 1;
     }
 }
 
@@ -525,7 +663,41 @@ impl World {
 
     pub fn get_pressure(&self, x: i32, y: i32) -> f32 {
         let idx = self.get_idx(x, y);
-        self.environment[idx].ambient_pressure
+        self.environment[idx].pressure
+    }
+
+    // what the updating cell sees, the previous frame for automata in double buffered mode
+    pub(crate) fn read_particle(&self, x: i32, y: i32) -> Particle {
+        if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
+            return EMPTY_CELL;
+        }
+        let idx = self.get_idx(x, y);
+        match self.previous.get(idx) {
+            Some(particle) if self.read_previous => *particle,
+            _ => self.particles[idx],
+        }
+    }
+
+    // what an update sees at (x, y): wall past the edges, or nothing when they are open
+    pub(crate) fn nbr_at(&self, x: i32, y: i32) -> Particle {
+        if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
+            // with open edges particles can move out of the world and are lost
+            if self.open_edges {
+                return EMPTY_CELL;
+            }
+            return Particle {
+                variant_type: variant_type::WALL,
+                ra: 0,
+                rb: 0,
+                clock: self.generation,
+                strength: 0,
+                modified: false,
+                velocity: Velocity { x: 0., y: 0. },
+                temperature: 0.,
+                state: metadata::NO_STATE,
+            };
+        }
+        self.read_particle(x, y)
     }
 
     pub fn get_particle(&self, x: i32, y: i32) -> Particle {
@@ -547,7 +719,8 @@ impl World {
         }
         let idx = self.get_idx(x, y);
         self.particles[idx].temperature =
-            (self.particles[idx].temperature + heat).clamp(-200., 9275.)
+            (self.particles[idx].temperature + heat).clamp(-200., 9275.);
+        self.touch(idx);
     }
 
     pub fn is_modified(&self) -> bool {
@@ -571,6 +744,170 @@ impl World {
         self.environment[idx].ambient_temperature = temperature;
     }
 
+    // tilt the whole level, 0 degrees is straight down
+    pub fn set_gravity_angle(&mut self, degrees: f32) {
+        self.gravity.set_angle(degrees);
+    }
+
+    pub fn add_gravity_point(&mut self, x: i32, y: i32, strength: f32, radius: f32) -> usize {
+        self.gravity.add_point(GravityPoint {
+            x,
+            y,
+            strength,
+            radius,
+        })
+    }
+
+    pub fn clear_gravity_points(&mut self) {
+        self.gravity.clear_points();
+    }
+
+    // turning the atmosphere on fills every cell with fresh air
+    pub fn set_atmosphere(&mut self, atmosphere: bool) {
+        self.atmosphere = atmosphere;
+        let mix = if atmosphere {
+            atmosphere::AIR
+        } else {
+            GasMix::default()
+        };
+        for env in self.environment.iter_mut() {
+            env.gas = mix;
+        }
+    }
+
+    pub fn get_gas(&self, x: i32, y: i32) -> GasMix {
+        let idx = self.get_idx(x, y);
+        self.environment[idx].gas
+    }
+
+    // let particles leave through the edges, each one that does is a LeftWorld event
+    pub fn set_open_edges(&mut self, open_edges: bool) {
+        self.open_edges = open_edges;
+    }
+
+    // order independent stepping for automata, movement still happens in place
+    pub fn set_double_buffered(&mut self, double_buffered: bool) {
+        self.double_buffered = double_buffered;
+        if !double_buffered {
+            self.previous = Vec::new();
+        }
+    }
+
+    // reconfigure a life species with a B/S rule string, e.g. "B36/S23"
+    pub fn set_life_rule(&mut self, variant: Variant, rule: &str) -> Result<(), String> {
+        self.life_rules.set(variant, rule)
+    }
+
+    // flip a switch between conducting and open
+    pub fn toggle_switch(&mut self, x: i32, y: i32) {
+        if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
+            return;
+        }
+        let idx = self.get_idx(x, y);
+        let mut particle = self.particles[idx];
+        if particle.get_variant() == Variant::SWCH {
+            let on = u8::from(!electricity::switch_on(self, &particle));
+            self.set_field(&mut particle, "on", Value::U8(on)).unwrap();
+            self.set_field(&mut particle, "spark", Value::U8(0))
+                .unwrap();
+            self.particles[idx] = particle;
+            self.touch(idx);
+        }
+    }
+
+    // inject electrons into a logic wire, ignored for anything that isn't WIRE
+    pub fn set_wire_state(&mut self, x: i32, y: i32, state: WireState) {
+        if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
+            return;
+        }
+        let idx = self.get_idx(x, y);
+        let mut particle = self.particles[idx];
+        if particle.get_variant() == Variant::WIRE {
+            self.set_field(&mut particle, "state", Value::U8(state as u8))
+                .unwrap();
+            self.particles[idx] = particle;
+            self.touch(idx);
+        }
+    }
+
+    // a field of the particle's element state, None if its element has no such field.
+    // particles only point into the state, what they point to is kept until the tick
+    // after they left the world
+    pub fn get_field(&self, particle: &Particle, name: &str) -> Option<Value> {
+        self.state.get(&self.elements, particle, name)
+    }
+
+    // only changes `particle`, copies of it keep what they had
+    pub fn set_field(
+        &mut self,
+        particle: &mut Particle,
+        name: &str,
+        value: Value,
+    ) -> Result<(), String> {
+        self.state.set(&self.elements, particle, name, value)
+    }
+
+    // shorthands for fields of a known kind, 0 if the particle doesn't have it
+    pub fn get_u8(&self, particle: &Particle, name: &str) -> u8 {
+        match self.get_field(particle, name) {
+            Some(Value::U8(v)) => v,
+            _ => 0,
+        }
+    }
+
+    pub fn get_u16(&self, particle: &Particle, name: &str) -> u16 {
+        match self.get_field(particle, name) {
+            Some(Value::U16(v)) => v,
+            _ => 0,
+        }
+    }
+
+    pub fn get_f32(&self, particle: &Particle, name: &str) -> f32 {
+        match self.get_field(particle, name) {
+            Some(Value::F32(v)) => v,
+            _ => 0.,
+        }
+    }
+
+    // every field of the particle with its value
+    pub fn fields(&self, particle: &Particle) -> Vec<(String, Value)> {
+        self.state.fields(&self.elements, particle)
+    }
+
+    // declare the state a runtime element keeps, built in elements have theirs fixed
+    pub fn register_fields(
+        &mut self,
+        variant_type: VariantType,
+        fields: Vec<Field>,
+    ) -> Result<(), String> {
+        if variant_type.source_variant != Variant::Custom {
+            return Err(format!(
+                "{} is built in, only runtime elements can declare fields",
+                variant_type.source_variant.get_name()
+            ));
+        }
+        metadata::check_fields(&fields)?;
+        match self.elements.set_fields(variant_type.custom, fields) {
+            true => Ok(()),
+            false => Err(format!("no runtime element {}", variant_type.custom)),
+        }
+    }
+
+    // events from the last tick, anything not drained is dropped when the next one starts
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, Event> {
+        self.events.drain(..)
+    }
+
+    // get a CountCrossed event whenever the number of `variant` particles crosses `threshold`
+    pub fn watch_count(&mut self, variant: Variant, threshold: usize) {
+        let watch = CountWatch::new(self, variant, threshold);
+        self.watches.push(watch);
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
     pub fn get_particle_count(&self) -> usize {
         self.particles
             .iter()
@@ -584,16 +921,25 @@ impl World {
         let particle = self.particles[idx];
         if particle.variant_type.has_flag(variant_type::FLAG_IMMUTABLE) {
             self.particles[idx] = EMPTY_CELL;
+            self.touch(idx);
         }
     }
 
     pub fn set_particle(&mut self, x: i32, y: i32, variant: Variant) {
+        self.set_element(x, y, self.elements.builtin(variant));
+    }
+
+    // like set_particle, for runtime elements as well as built in ones
+    pub fn set_element(&mut self, x: i32, y: i32, variant_type: VariantType) {
         if x < 0 || x > self.width - 1 || y < 0 || y > self.height - 1 {
             return;
         }
 
-        // if the particle is already set to the same variant, don't do anything
-        if self.get_particle(x, y).get_variant() == variant {
+        // if the particle is already set to the same element, don't do anything
+        let current = self.get_particle(x, y).variant_type;
+        if current.source_variant == variant_type.source_variant
+            && current.custom == variant_type.custom
+        {
             return;
         }
 
@@ -603,7 +949,60 @@ impl World {
         }
 
         let idx = self.get_idx(x, y);
-        self.particles[idx] = Particle::new(*VariantType::from_variant(variant), 0, 0);
+        self.particles[idx] = Particle::new(variant_type, 0, 0);
+        self.touch(idx);
+    }
+
+    // add (or replace) a runtime element, particles of it already in the world are updated
+    pub fn register_element(
+        &mut self,
+        name: &str,
+        code: &str,
+        variant_type: VariantType,
+        behaviour: Option<Arc<dyn Behaviour>>,
+    ) -> VariantType {
+        let variant_type = self.elements.register(name, code, variant_type, behaviour);
+        for idx in 0..self.particles.len() {
+            let particle = &mut self.particles[idx];
+            if particle.get_variant() == Variant::Custom
+                && particle.variant_type.custom == variant_type.custom
+            {
+                particle.variant_type = variant_type;
+                self.touch(idx);
+            }
+        }
+        variant_type
+    }
+
+    // change a built in element's type, particles of it already in the world are updated
+    pub fn override_element(&mut self, variant_type: VariantType) {
+        let variant = variant_type.source_variant;
+        if variant == Variant::Custom {
+            return;
+        }
+        self.elements.override_builtin(variant_type);
+        self.refresh_builtin(variant);
+    }
+
+    // back to the compiled in type
+    pub fn reset_element(&mut self, variant: Variant) {
+        self.elements.reset_builtin(variant);
+        self.refresh_builtin(variant);
+    }
+
+    fn refresh_builtin(&mut self, variant: Variant) {
+        let variant_type = self.elements.builtin(variant);
+        for idx in 0..self.particles.len() {
+            if self.particles[idx].get_variant() == variant {
+                self.particles[idx].variant_type = variant_type;
+                self.touch(idx);
+            }
+        }
+    }
+
+    // built in elements by short code, runtime ones by name or code
+    pub fn find_element(&self, name: &str) -> Option<VariantType> {
+        self.elements.lookup(name)
     }
 }
 
@@ -616,4 +1015,96 @@ mod tests {
         let world = World::new(100, 100);
         assert_eq!(world.get_particle(0, 0).get_variant(), Variant::Empty);
     }
+
+    #[test]
+    fn test_tilted_gravity() {
+        let mut world = World::new(10, 10);
+        world.set_gravity_angle(-90.);
+        world.set_particle(2, 5, Variant::Sand);
+        for _ in 0..20 {
+            world.tick();
+        }
+        // sideways "down" is +x, the sand drifts diagonally but ends up on the right wall
+        assert!((0..10).any(|y| world.get_particle(9, y).get_variant() == Variant::Sand));
+        assert_eq!(world.get_particle_count(), 1);
+    }
+
+    fn column(world: &World) -> Vec<Variant> {
+        (0..world.height)
+            .map(|y| world.get_particle(0, y).get_variant())
+            .collect()
+    }
+
+    #[test]
+    fn test_density_layering() {
+        // one cell wide so everything has to pass through everything else
+        let mut world = World::new(1, 6);
+        world.set_particle(0, 2, Variant::Water);
+        world.set_particle(0, 3, Variant::Water);
+        world.set_particle(0, 4, Variant::Oil);
+        world.set_particle(0, 5, Variant::Oil);
+        for _ in 0..20 {
+            world.tick();
+        }
+        use Variant::*;
+        assert_eq!(column(&world), vec![Empty, Empty, Oil, Oil, Water, Water]);
+    }
+
+    #[test]
+    fn test_gas_buoyancy() {
+        let mut world = World::new(1, 8);
+        world.set_particle(0, 3, Variant::CO2);
+        world.set_particle(0, 4, Variant::NITR);
+        world.set_particle(0, 5, Variant::HELM);
+        world.set_particle(0, 7, Variant::Sand);
+        for _ in 0..40 {
+            world.tick();
+        }
+        // helium floats to the top, co2 pools on the sand and nitrogen sits in between
+        let col = column(&world);
+        assert_eq!(col[0], Variant::HELM);
+        assert_eq!(col[6], Variant::CO2);
+        assert_eq!(col[7], Variant::Sand);
+        assert!(col[1..6].contains(&Variant::NITR));
+    }
+
+    fn live_cells(world: &World) -> Vec<(i32, i32)> {
+        let mut cells = vec![];
+        for y in 0..world.height {
+            for x in 0..world.width {
+                if world.get_particle(x, y).get_variant() == Variant::GOL {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn test_double_buffered_life() {
+        let mut world = World::new(12, 12);
+        world.set_double_buffered(true);
+
+        // blinker
+        for x in 1..4 {
+            world.set_particle(x, 2, Variant::GOL);
+        }
+        world.tick();
+        assert_eq!(live_cells(&world), vec![(2, 1), (2, 2), (2, 3)]);
+        world.tick();
+        assert_eq!(live_cells(&world), vec![(1, 2), (2, 2), (3, 2)]);
+
+        // glider moves one cell diagonally every four generations
+        world.reset();
+        let glider = [(6, 5), (7, 6), (5, 7), (6, 7), (7, 7)];
+        for (x, y) in glider {
+            world.set_particle(x, y, Variant::GOL);
+        }
+        for _ in 0..4 {
+            world.tick();
+        }
+        let mut moved: Vec<(i32, i32)> = glider.iter().map(|(x, y)| (x + 1, y + 1)).collect();
+        moved.sort_by_key(|(x, y)| (*y, *x));
+        assert_eq!(live_cells(&world), moved);
+    }
 }
@@ -1,4 +1,13 @@
-use silica_engine::prelude::*;
+use silica_engine::{
+    definitions::DefinitionsWatcher,
+    group::ElementManager,
+    prelude::*,
+    probe,
+    record::{self, Format, Recording},
+    script,
+    snapshot::{self, Grid, Snapshot},
+    stats::Stats,
+};
 
 use godot::{engine::Image, prelude::*};
 struct SilicaExtension;
@@ -9,6 +18,12 @@ unsafe impl ExtensionLibrary for SilicaExtension {}
 #[class(base=Node)]
 struct GDWorld {
     world: World,
+    groups: ElementManager,
+    definitions: Option<DefinitionsWatcher>,
+    renderer: Renderer,
+    stats: Stats,
+    // runtime errors of every loaded script, drained by take_script_errors
+    script_errors: Vec<script::ScriptErrors>,
     #[base]
     node: Base<Node>,
 }
@@ -20,12 +35,26 @@ impl NodeVirtual for GDWorld {
         let world = World::new(256, 256);
         let image = Image::new();
 
-        GDWorld { world, node: node }
+        GDWorld {
+            world,
+            groups: ElementManager::builtin(),
+            definitions: None,
+            renderer: Renderer::new(256, 256),
+            stats: Stats::default(),
+            script_errors: Vec::new(),
+            node: node,
+        }
     }
 
     fn physics_process(&mut self, delta: f64) {
+        if let Some(definitions) = self.definitions.as_mut() {
+            if let Some(Err(err)) = definitions.poll(&mut self.world, &mut self.groups) {
+                godot_print!("{}", err);
+            }
+        }
         if self.world.running {
             self.world.tick();
+            self.stats.record(&self.world);
         }
 
         // create image and edit pixels based on world.world.particle value
@@ -34,22 +63,262 @@ impl NodeVirtual for GDWorld {
 
 #[godot_api]
 impl GDWorld {
+    // the whole frame, only cells that changed since the last call are recoloured
+    #[func]
+    pub fn get_data(&mut self) -> PackedByteArray {
+        self.renderer.update(&self.world);
+        PackedByteArray::from(self.renderer.pixels())
+    }
+
+    // recolour what changed since the last call without copying the frame out,
+    // then upload each of get_dirty_rects with get_rect_data
+    #[func]
+    pub fn update_view(&mut self) {
+        self.renderer.update(&self.world);
+    }
+
+    // the pixels of one region packed row after row, empty if it isn't inside the world
+    #[func]
+    pub fn get_rect_data(&self, x: i64, y: i64, width: i64, height: i64) -> PackedByteArray {
+        let (x, y, width, height) = (x as i32, y as i32, width as i32, height as i32);
+        if x < 0
+            || y < 0
+            || width <= 0
+            || height <= 0
+            || x + width > self.renderer.width()
+            || y + height > self.renderer.height()
+        {
+            return PackedByteArray::new();
+        }
+        let mut pixels = Vec::new();
+        self.renderer.copy_rect(
+            Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+            &mut pixels,
+        );
+        PackedByteArray::from(pixels.as_slice())
+    }
+
+    // x, y, width, height of each region the last get_data or update_view changed
+    #[func]
+    pub fn get_dirty_rects(&self) -> PackedInt32Array {
+        let rects: Vec<i32> = self
+            .renderer
+            .dirty()
+            .iter()
+            .flat_map(|r| [r.x, r.y, r.width, r.height])
+            .collect();
+        PackedInt32Array::from(rects.as_slice())
+    }
+
+    // 0 normal, 1 heat, 2 pressure, 3 velocity, 4 element, 5 modified last tick
+    #[func]
+    pub fn set_view(&mut self, view: i64) {
+        self.renderer.set_view(View::from_u8(view as u8));
+    }
+
+    #[func]
+    pub fn set_glow(&mut self, glow: bool) {
+        self.renderer.set_glow(glow);
+    }
+
+    // run the world for `ticks` ticks into a .gif or .png animation in the current view,
+    // returns the error or an empty string
+    #[func]
+    pub fn record(
+        &mut self,
+        path: GodotString,
+        ticks: i64,
+        frame_skip: i64,
+        scale: i64,
+    ) -> GodotString {
+        let path = path.to_string();
+        let recording = Recording {
+            format: match path.ends_with(".gif") {
+                true => Format::Gif,
+                false => Format::Apng,
+            },
+            ticks: ticks as u32,
+            frame_skip: frame_skip as u32,
+            scale: scale as u32,
+            view: self.renderer.view(),
+            glow: self.renderer.glow(),
+            ..Default::default()
+        };
+        match record::record_to_file(&mut self.world, &recording, &path) {
+            Ok(_) => GodotString::new(),
+            Err(err) => GodotString::from(err),
+        }
+    }
+
+    // png bytes of the world in the current view, for thumbnails. grid is the cell spacing
+    // of the grid lines, 0 for none
+    #[func]
+    pub fn get_snapshot(&self, scale: i64, alpha: bool, grid: i64) -> PackedByteArray {
+        let snapshot = Snapshot {
+            scale: scale as u32,
+            alpha,
+            grid: (grid > 0).then(|| Grid::new(grid as u32)),
+            view: self.renderer.view(),
+            ..Default::default()
+        };
+        match snapshot::snapshot_png(&self.world, &snapshot) {
+            Ok(png) => PackedByteArray::from(png.as_slice()),
+            Err(err) => {
+                godot_print!("{}", err);
+                PackedByteArray::new()
+            }
+        }
+    }
+
+    // what is at a cell and what it may turn into, as tooltip text. empty outside the world
+    #[func]
+    pub fn probe(&self, x: i32, y: i32) -> GodotString {
+        match probe::probe(&self.world, x, y) {
+            Some(probe) => GodotString::from(probe.to_string()),
+            None => GodotString::new(),
+        }
+    }
+
+    // a stats column over the recorded window, oldest first: "particles", "active",
+    // "sleeping", "total_heat", "average_temperature", "mass_powder" and so on, or an element code
+    #[func]
+    pub fn get_stat_series(&self, column: GodotString) -> PackedFloat32Array {
+        PackedFloat32Array::from(self.stats.series(&column.to_string()).as_slice())
+    }
+
+    // element codes seen in the recorded window
+    #[func]
+    pub fn get_stat_codes(&self) -> PackedStringArray {
+        let codes: Vec<String> = self.stats.codes().into_iter().map(String::from).collect();
+        strings(codes.iter())
+    }
+
+    // samples every `every` ticks, keeping the last `capacity`. clears what was recorded
+    #[func]
+    pub fn set_stats_window(&mut self, capacity: i64, every: i64) {
+        self.stats = Stats {
+            capacity: capacity.max(1) as usize,
+            every: every.max(1) as u32,
+            ..Default::default()
+        };
+    }
+
+    // returns the error or an empty string
+    #[func]
+    pub fn save_stats_csv(&self, path: GodotString) -> GodotString {
+        match self.stats.save_csv(&path.to_string()) {
+            Ok(_) => GodotString::new(),
+            Err(err) => GodotString::from(err),
+        }
+    }
+
+    #[func]
+    pub fn set_gravity_angle(&mut self, degrees: f32) {
+        self.world.set_gravity_angle(degrees);
+    }
+
+    #[func]
+    pub fn add_gravity_point(&mut self, x: i32, y: i32, strength: f32, radius: f32) -> i64 {
+        self.world.add_gravity_point(x, y, strength, radius) as i64
+    }
+
+    #[func]
+    pub fn clear_gravity_points(&mut self) {
+        self.world.clear_gravity_points();
+    }
+
+    #[func]
+    pub fn set_double_buffered(&mut self, double_buffered: bool) {
+        self.world.set_double_buffered(double_buffered);
+    }
+
+    #[func]
+    pub fn set_atmosphere(&mut self, atmosphere: bool) {
+        self.world.set_atmosphere(atmosphere);
+    }
+
+    #[func]
+    pub fn toggle_switch(&mut self, x: i32, y: i32) {
+        self.world.toggle_switch(x, y);
+    }
+
+    #[func]
+    pub fn pulse_wire(&mut self, x: i32, y: i32) {
+        self.world.set_wire_state(x, y, WireState::Head);
+    }
+
+    // group names in picker order
     #[func]
-    pub fn get_data(&self) -> PackedByteArray {
-        let mut data = PackedByteArray::new();
-        data.resize(self.world.width as usize * self.world.height as usize * 4);
-
-        for x in 0..self.world.width as usize {
-            for y in 0..self.world.height as usize {
-                let index = (x + y * self.world.width as usize) * 4;
-                let particle = self.world.get_particle(x as i32, y as i32);
-                let color = variant_type(particle.variant).color;
-                data.set(index, color.0);
-                data.set(index + 1, color.1);
-                data.set(index + 2, color.2);
-                data.set(index + 3, 255);
+    pub fn get_group_names(&self) -> PackedStringArray {
+        strings(self.groups.get_group_names().iter())
+    }
+
+    // short codes of the elements a picker lists for a group
+    #[func]
+    pub fn get_group_codes(&self, group: GodotString, debug: bool) -> PackedStringArray {
+        let entries = self.groups.get_entries(&group.to_string(), debug);
+        strings(entries.iter().map(|e| &e.code))
+    }
+
+    // short codes of the elements whose name or code matches
+    #[func]
+    pub fn search_elements(&self, query: GodotString) -> PackedStringArray {
+        let found = self.groups.search(&query.to_string());
+        strings(found.iter().map(|e| &e.code))
+    }
+
+    // name and description of an element, empty if it isn't in the catalogue
+    #[func]
+    pub fn describe_element(&self, code: GodotString) -> PackedStringArray {
+        match self.groups.find(&code.to_string()) {
+            Some(info) => strings([&info.name, &info.description].into_iter()),
+            None => PackedStringArray::new(),
+        }
+    }
+
+    // define elements from rhai source, returns the error or an empty string
+    #[func]
+    pub fn load_script(&mut self, source: GodotString) -> GodotString {
+        match script::load_script(&mut self.world, &mut self.groups, &source.to_string()) {
+            Ok(script) => {
+                self.script_errors.push(script.errors);
+                GodotString::new()
             }
+            Err(err) => GodotString::from(err),
         }
-        data
     }
+
+    // errors scripted elements ran into while updating since the last call
+    #[func]
+    pub fn take_script_errors(&mut self) -> PackedStringArray {
+        let errors: Vec<String> = self.script_errors.iter().flat_map(|e| e.take()).collect();
+        strings(errors.iter())
+    }
+
+    // element types and groups from a .toml or .ron file, reloaded whenever it is saved.
+    // returns the error or an empty string
+    #[func]
+    pub fn load_definitions(&mut self, path: GodotString) -> GodotString {
+        let mut definitions = DefinitionsWatcher::new(path.to_string());
+        let result = definitions.poll(&mut self.world, &mut self.groups);
+        self.definitions = Some(definitions);
+        match result {
+            Some(Err(err)) => GodotString::from(err),
+            Some(Ok(_)) => GodotString::new(),
+            None => GodotString::from(format!("can't read {}", path)),
+        }
+    }
+}
+
+fn strings<'a>(items: impl Iterator<Item = &'a String>) -> PackedStringArray {
+    let mut array = PackedStringArray::new();
+    for item in items {
+        array.push(GodotString::from(item.as_str()));
+    }
+    array
 }